@@ -0,0 +1,93 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rosso::{commands, resp, resp_cmd};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const COMMANDS: usize = 100_000;
+
+fn pipeline() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for i in 0..COMMANDS {
+        let key = format!("key:{}", i);
+        let value = format!("value:{}", i);
+        bytes.extend_from_slice(
+            format!(
+                "*5\r\n$3\r\nSET\r\n${}\r\n{}\r\n${}\r\n{}\r\n$2\r\nEX\r\n$2\r\n60\r\n",
+                key.len(),
+                key,
+                value.len(),
+                value
+            )
+            .as_bytes(),
+        );
+    }
+    bytes
+}
+
+fn measure(name: &str, f: impl FnOnce() -> usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = std::time::Instant::now();
+    let commands = f();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    assert_eq!(COMMANDS, commands);
+    println!(
+        "{:<24} {:>8.1} ns/command {:>6.2} allocations/command",
+        name,
+        elapsed.as_nanos() as f64 / commands as f64,
+        allocations as f64 / commands as f64
+    );
+}
+
+fn main() {
+    let bytes = pipeline();
+
+    measure("async parse", || {
+        let mut reader = futures_lite::io::BufReader::new(bytes.as_slice());
+        smol::block_on(async {
+            let mut commands = 0;
+            while let Ok(value) = resp::parse(&mut reader).await {
+                resp_cmd::parse_command(value).unwrap();
+                commands += 1;
+            }
+            commands
+        })
+    });
+
+    measure("incremental decoder", || {
+        let mut decoder = resp::Decoder::new(resp::Limits::default());
+        let mut commands = 0;
+        for chunk in bytes.chunks(16 * 1024) {
+            decoder.extend(chunk);
+            while let Some(request) = decoder.decode().unwrap() {
+                let spec = request.args().next().and_then(commands::lookup);
+                resp_cmd::parse_request(&request, spec).unwrap();
+                commands += 1;
+            }
+        }
+        commands
+    });
+}
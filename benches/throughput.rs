@@ -0,0 +1,52 @@
+const CLIENTS: usize = 32;
+const PIPELINES: usize = 50;
+const PIPELINE_LEN: usize = 100;
+
+fn run_clients(url: &str) -> usize {
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|c| {
+            let client = redis::Client::open(url).unwrap();
+            std::thread::spawn(move || {
+                let mut con = client.get_connection().unwrap();
+                let key = format!("client:{}", c);
+                for _ in 0..PIPELINES {
+                    let mut pipe = redis::pipe();
+                    for i in 0..PIPELINE_LEN / 2 {
+                        pipe.cmd("SET").arg(&key).arg(i).ignore();
+                        pipe.cmd("GET").arg(&key).ignore();
+                    }
+                    pipe.exec(&mut con).unwrap();
+                }
+                PIPELINES * PIPELINE_LEN
+            })
+        })
+        .collect();
+    clients.into_iter().map(|c| c.join().unwrap()).sum()
+}
+
+fn measure(threads: usize) {
+    let server = rosso::server::Server::new()
+        .threads(threads)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let url = format!("redis://{}", server.local_addr());
+
+    let start = std::time::Instant::now();
+    let commands = run_clients(&url);
+    let elapsed = start.elapsed();
+    println!(
+        "{:>3} threads {:>12.0} commands/s",
+        threads,
+        commands as f64 / elapsed.as_secs_f64()
+    );
+    server.shutdown().unwrap();
+}
+
+fn main() {
+    let mut threads = vec![1, 4, rosso::server::default_threads()];
+    threads.sort();
+    threads.dedup();
+    for t in threads {
+        measure(t);
+    }
+}
@@ -0,0 +1,525 @@
+use futures_lite::future;
+use smol::channel::{Receiver, Sender};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::redis;
+
+// Every connected client, shared by all the connections of a server.
+pub struct Registry {
+    next_id: AtomicU64,
+    clients: Mutex<BTreeMap<u64, Arc<Client>>>,
+    pause: Mutex<Option<Pause>>,
+    // lets every command skip locking `pause` unless a pause was requested
+    pausing: AtomicBool,
+}
+
+pub struct Client {
+    pub id: u64,
+    addr: SocketAddr,
+    laddr: SocketAddr,
+    created: Instant,
+    state: Mutex<State>,
+    // closing the channel wakes up the connection, which then hangs up
+    kill: Sender<()>,
+    killed: Receiver<()>,
+}
+
+struct State {
+    name: String,
+    lib_name: String,
+    lib_ver: String,
+    last_interaction: Instant,
+    cmd: &'static str,
+    subcommand: Vec<u8>,
+    protocol: i64,
+}
+
+struct Pause {
+    until: Instant,
+    mode: redis::PauseMode,
+    // closed by CLIENT UNPAUSE to wake up the paused clients early
+    unpause: Sender<()>,
+    unpaused: Receiver<()>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            next_id: AtomicU64::new(1),
+            clients: Mutex::new(BTreeMap::new()),
+            pause: Mutex::new(None),
+            pausing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn register(&self, addr: SocketAddr, laddr: SocketAddr) -> Arc<Client> {
+        let now = Instant::now();
+        let (kill, killed) = smol::channel::bounded(1);
+        let client = Arc::new(Client {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            addr,
+            laddr,
+            created: now,
+            state: Mutex::new(State {
+                name: String::new(),
+                lib_name: String::new(),
+                lib_ver: String::new(),
+                last_interaction: now,
+                cmd: "NULL",
+                subcommand: Vec::new(),
+                protocol: 2,
+            }),
+            kill,
+            killed,
+        });
+        lock(&self.clients).insert(client.id, client.clone());
+        client
+    }
+
+    pub fn unregister(&self, client: &Client) {
+        lock(&self.clients).remove(&client.id);
+    }
+
+    // Waits for CLIENT PAUSE to be over, if it applies to a command.
+    pub async fn paused(&self, write: bool) {
+        while self.pausing.load(Ordering::Relaxed) {
+            let (until, unpaused) = match &mut *lock(&self.pause) {
+                Some(p) if p.until <= Instant::now() => {
+                    self.pausing.store(false, Ordering::Relaxed);
+                    return;
+                }
+                Some(p) if write || p.mode == redis::PauseMode::All => {
+                    (p.until, p.unpaused.clone())
+                }
+                _ => return,
+            };
+            future::or(
+                async {
+                    smol::Timer::at(until).await;
+                },
+                async {
+                    let _ = unpaused.recv().await;
+                },
+            )
+            .await;
+        }
+    }
+
+    pub fn call(&self, client: &Client, command: redis::ClientCommand) -> redis::Result {
+        match command {
+            redis::ClientCommand::Id => redis::Result::Integer(client.id as i64),
+            redis::ClientCommand::Info => {
+                redis::Result::VerbatimString(client.info(Instant::now()) + "\n")
+            }
+            redis::ClientCommand::List { kind, ids } => {
+                // every client is a normal one: there are no replicas or subscribers
+                if kind.is_some_and(|k| k != redis::ClientType::Normal) {
+                    return redis::Result::VerbatimString(String::new());
+                }
+                let now = Instant::now();
+                let list = lock(&self.clients)
+                    .values()
+                    .filter(|c| ids.is_empty() || ids.contains(&redis::Integer(c.id as i64)))
+                    .map(|c| c.info(now) + "\n")
+                    .collect();
+                redis::Result::VerbatimString(list)
+            }
+            redis::ClientCommand::SetName {
+                name: redis::String(name),
+            } => match valid_name(name) {
+                Some(name) => {
+                    client.state().name = name;
+                    redis::Result::Ok
+                }
+                None => redis::Result::Error(redis::Error::Generic(
+                    "Client names cannot contain spaces, newlines or special characters."
+                        .to_string(),
+                )),
+            },
+            redis::ClientCommand::GetName => match &client.state().name {
+                name if name.is_empty() => redis::Result::Null,
+                name => redis::Result::BulkString(name.as_bytes().to_vec()),
+            },
+            redis::ClientCommand::SetInfo {
+                attribute,
+                value: redis::String(value),
+            } => {
+                let field = match attribute {
+                    redis::ClientAttribute::LibName => "lib-name",
+                    redis::ClientAttribute::LibVer => "lib-ver",
+                };
+                let Some(value) = valid_name(value) else {
+                    return redis::Result::Error(redis::Error::Generic(format!(
+                        "{} cannot contain spaces, newlines or special characters.",
+                        field
+                    )));
+                };
+                let mut state = client.state();
+                match attribute {
+                    redis::ClientAttribute::LibName => state.lib_name = value,
+                    redis::ClientAttribute::LibVer => state.lib_ver = value,
+                }
+                redis::Result::Ok
+            }
+            redis::ClientCommand::KillAddr {
+                addr: redis::String(addr),
+            } => {
+                let filters = [redis::KillFilter::Addr(redis::String(addr))];
+                if self.kill(client, &filters, false) > 0 {
+                    redis::Result::Ok
+                } else {
+                    redis::Result::Error(redis::Error::Generic("No such client".to_string()))
+                }
+            }
+            redis::ClientCommand::Kill { filters } => {
+                let skip_me = filters
+                    .iter()
+                    .rev()
+                    .find_map(|f| match f {
+                        redis::KillFilter::SkipMe(skip) => Some(*skip),
+                        _ => None,
+                    })
+                    .unwrap_or(true);
+                redis::Result::Integer(self.kill(client, &filters, skip_me) as i64)
+            }
+            redis::ClientCommand::Pause {
+                timeout: redis::Integer(timeout),
+                mode,
+            } => {
+                let Some(until) = Instant::now().checked_add(Duration::from_millis(timeout as u64))
+                else {
+                    return redis::Result::Error(redis::Error::Generic(
+                        "timeout is out of range".to_string(),
+                    ));
+                };
+                let mut pause = lock(&self.pause);
+                match &mut *pause {
+                    // a new pause can only make the current one longer or stricter
+                    Some(p) if p.until > Instant::now() => {
+                        p.until = p.until.max(until);
+                        if mode == redis::PauseMode::All {
+                            p.mode = mode;
+                        }
+                    }
+                    _ => {
+                        let (unpause, unpaused) = smol::channel::bounded(1);
+                        *pause = Some(Pause {
+                            until,
+                            mode,
+                            unpause,
+                            unpaused,
+                        });
+                        self.pausing.store(true, Ordering::Relaxed);
+                    }
+                }
+                redis::Result::Ok
+            }
+            redis::ClientCommand::Unpause => {
+                if let Some(p) = lock(&self.pause).take() {
+                    self.pausing.store(false, Ordering::Relaxed);
+                    p.unpause.close();
+                }
+                redis::Result::Ok
+            }
+        }
+    }
+
+    fn kill(&self, me: &Client, filters: &[redis::KillFilter], skip_me: bool) -> usize {
+        let now = Instant::now();
+        let clients = lock(&self.clients);
+        let mut killed = 0;
+        for client in clients.values() {
+            if (skip_me && client.id == me.id) || !filters.iter().all(|f| client.matches(f, now)) {
+                continue;
+            }
+            client.kill.close();
+            killed += 1;
+        }
+        killed
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn is_killed(&self) -> bool {
+        self.kill.is_closed()
+    }
+
+    // Resolves once the client has been killed.
+    pub async fn killed(&self) {
+        let _ = self.killed.recv().await;
+    }
+
+    // Records a command about to run: `subcommand` is only given for
+    // container commands like CLIENT.
+    pub fn interact(&self, cmd: &'static str, subcommand: Option<&[u8]>) {
+        let mut state = self.state();
+        state.last_interaction = Instant::now();
+        state.cmd = cmd;
+        state.subcommand.clear();
+        // anything else cannot be a subcommand and would garble CLIENT LIST
+        if let Some(subcommand) =
+            subcommand.filter(|s| s.len() <= 32 && s.iter().all(u8::is_ascii_graphic))
+        {
+            state
+                .subcommand
+                .extend(subcommand.iter().map(u8::to_ascii_lowercase));
+        }
+    }
+
+    pub fn set_protocol(&self, version: i64) {
+        self.state().protocol = version;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn matches(&self, filter: &redis::KillFilter, now: Instant) -> bool {
+        match filter {
+            redis::KillFilter::Id(redis::Integer(id)) => self.id as i64 == *id,
+            redis::KillFilter::Type(kind) => *kind == redis::ClientType::Normal,
+            redis::KillFilter::User(redis::String(user)) => user == b"default",
+            redis::KillFilter::Addr(redis::String(addr)) => {
+                self.addr.to_string().as_bytes() == addr.as_slice()
+            }
+            redis::KillFilter::LAddr(redis::String(laddr)) => {
+                self.laddr.to_string().as_bytes() == laddr.as_slice()
+            }
+            redis::KillFilter::SkipMe(_) => true,
+            redis::KillFilter::MaxAge(redis::Integer(age)) => {
+                now.duration_since(self.created).as_secs() as i64 >= *age
+            }
+        }
+    }
+
+    fn info(&self, now: Instant) -> String {
+        let state = self.state();
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags=N db=0 cmd={}{}{} user=default resp={} lib-name={} lib-ver={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            state.name,
+            now.duration_since(self.created).as_secs(),
+            now.duration_since(state.last_interaction).as_secs(),
+            state.cmd,
+            if state.subcommand.is_empty() { "" } else { "|" },
+            String::from_utf8_lossy(&state.subcommand),
+            state.protocol,
+            state.lib_name,
+            state.lib_ver
+        )
+    }
+}
+
+// Names end up in space-separated CLIENT LIST lines, so like Redis only
+// printable characters other than spaces are allowed.
+fn valid_name(name: Vec<u8>) -> Option<String> {
+    if name.iter().all(|c| (b'!'..=b'~').contains(c)) {
+        String::from_utf8(name).ok()
+    } else {
+        None
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_id() {
+        let registry = Registry::new();
+        let first = registry.register(addr(1000), addr(6379));
+        let second = registry.register(addr(1001), addr(6379));
+
+        assert_eq!(
+            registry.call(&first, redis::ClientCommand::Id),
+            redis::Result::Integer(1)
+        );
+        assert_eq!(
+            registry.call(&second, redis::ClientCommand::Id),
+            redis::Result::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_setname_and_getname() {
+        let registry = Registry::new();
+        let client = registry.register(addr(1000), addr(6379));
+
+        assert_eq!(
+            registry.call(&client, redis::ClientCommand::GetName),
+            redis::Result::Null
+        );
+
+        let result = registry.call(
+            &client,
+            redis::ClientCommand::SetName {
+                name: redis::String(b"worker".to_vec()),
+            },
+        );
+        assert_eq!(result, redis::Result::Ok);
+        assert_eq!(
+            registry.call(&client, redis::ClientCommand::GetName),
+            redis::Result::BulkString(b"worker".to_vec())
+        );
+
+        let result = registry.call(
+            &client,
+            redis::ClientCommand::SetName {
+                name: redis::String(b"two words".to_vec()),
+            },
+        );
+        assert!(matches!(result, redis::Result::Error(_)));
+    }
+
+    #[test]
+    fn test_info_and_list() {
+        let registry = Registry::new();
+        let first = registry.register(addr(1000), addr(6379));
+        let second = registry.register(addr(1001), addr(6379));
+        registry.call(
+            &first,
+            redis::ClientCommand::SetInfo {
+                attribute: redis::ClientAttribute::LibName,
+                value: redis::String(b"rosso-tests".to_vec()),
+            },
+        );
+        second.interact("get", None);
+        first.interact("client", Some(b"INFO"));
+
+        let redis::Result::VerbatimString(info) = registry.call(&first, redis::ClientCommand::Info)
+        else {
+            panic!("expected a verbatim string");
+        };
+        assert!(info.starts_with("id=1 addr=127.0.0.1:1000 laddr=127.0.0.1:6379 name= "));
+        assert!(info.contains(" cmd=client|info "));
+        assert!(info.contains(" lib-name=rosso-tests "));
+
+        let redis::Result::VerbatimString(list) = registry.call(
+            &first,
+            redis::ClientCommand::List {
+                kind: None,
+                ids: vec![],
+            },
+        ) else {
+            panic!("expected a verbatim string");
+        };
+        let lines = list.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains(" cmd=get "));
+
+        let result = registry.call(
+            &first,
+            redis::ClientCommand::List {
+                kind: None,
+                ids: vec![redis::Integer(2)],
+            },
+        );
+        assert!(matches!(result, redis::Result::VerbatimString(l) if l.starts_with("id=2 ")));
+
+        let result = registry.call(
+            &first,
+            redis::ClientCommand::List {
+                kind: Some(redis::ClientType::PubSub),
+                ids: vec![],
+            },
+        );
+        assert_eq!(result, redis::Result::VerbatimString(String::new()));
+    }
+
+    #[test]
+    fn test_kill() {
+        let registry = Registry::new();
+        let me = registry.register(addr(1000), addr(6379));
+        let other = registry.register(addr(1001), addr(6379));
+
+        let result = registry.call(
+            &me,
+            redis::ClientCommand::Kill {
+                filters: vec![redis::KillFilter::LAddr(redis::String(
+                    b"127.0.0.1:6379".to_vec(),
+                ))],
+            },
+        );
+        assert_eq!(result, redis::Result::Integer(1));
+        assert!(!me.is_killed());
+        assert!(other.is_killed());
+
+        let result = registry.call(
+            &me,
+            redis::ClientCommand::KillAddr {
+                addr: redis::String(b"127.0.0.1:9999".to_vec()),
+            },
+        );
+        assert!(matches!(result, redis::Result::Error(_)));
+
+        let result = registry.call(
+            &me,
+            redis::ClientCommand::Kill {
+                filters: vec![
+                    redis::KillFilter::Id(redis::Integer(1)),
+                    redis::KillFilter::SkipMe(false),
+                ],
+            },
+        );
+        assert_eq!(result, redis::Result::Integer(1));
+        assert!(me.is_killed());
+    }
+
+    #[test]
+    fn test_pause_and_unpause() {
+        let registry = Registry::new();
+        let client = registry.register(addr(1000), addr(6379));
+
+        registry.call(
+            &client,
+            redis::ClientCommand::Pause {
+                timeout: redis::Integer(50),
+                mode: redis::PauseMode::Write,
+            },
+        );
+        let start = Instant::now();
+        // reads are not affected by a WRITE pause
+        smol::block_on(registry.paused(false));
+        assert!(start.elapsed() < Duration::from_millis(50));
+        smol::block_on(registry.paused(true));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        registry.call(
+            &client,
+            redis::ClientCommand::Pause {
+                timeout: redis::Integer(10_000),
+                mode: redis::PauseMode::All,
+            },
+        );
+        let start = Instant::now();
+        smol::block_on(future::zip(registry.paused(false), async {
+            smol::Timer::after(Duration::from_millis(20)).await;
+            registry.call(&client, redis::ClientCommand::Unpause);
+        }));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
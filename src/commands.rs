@@ -0,0 +1,924 @@
+use crate::redis;
+
+#[derive(Debug)]
+pub struct Spec {
+    pub name: &'static str,
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    pub acl_categories: &'static [&'static str],
+    pub keys: Option<Keys>,
+    pub group: &'static str,
+    pub since: &'static str,
+    pub summary: &'static str,
+    pub complexity: &'static str,
+    // whether the first argument is a subcommand, like in `CLIENT ID`
+    pub container: bool,
+}
+
+#[derive(Debug)]
+pub struct Keys {
+    pub first: i64,
+    pub last: i64,
+    pub step: i64,
+    pub flags: &'static [&'static str],
+}
+
+pub static TABLE: &[Spec] = &[
+    Spec {
+        name: "append",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "insert"],
+        }),
+        group: "string",
+        since: "2.0.0",
+        summary: "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+        complexity: "O(1). The amortized time complexity is O(1) assuming the appended value is small and the already present value is of any size, since the dynamic string library used by Redis will double the free space available on every reallocation.",
+        container: false,
+    },
+    Spec {
+        name: "client",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["@slow"],
+        keys: None,
+        group: "connection",
+        since: "2.4.0",
+        summary: "A container for client connection commands.",
+        complexity: "Depends on subcommand.",
+        container: true,
+    },
+    Spec {
+        name: "command",
+        arity: -1,
+        flags: &["loading", "stale"],
+        acl_categories: &["@slow", "@connection"],
+        keys: None,
+        group: "server",
+        since: "2.8.13",
+        summary: "Returns detailed information about all commands.",
+        complexity: "O(N) where N is the total number of Redis commands",
+        container: true,
+    },
+    Spec {
+        name: "config",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["@slow"],
+        keys: None,
+        group: "server",
+        since: "2.0.0",
+        summary: "A container for server configuration commands.",
+        complexity: "Depends on subcommand.",
+        container: true,
+    },
+    Spec {
+        name: "copy",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@keyspace", "@write", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 2,
+            step: 1,
+            flags: &["RW"],
+        }),
+        group: "generic",
+        since: "6.2.0",
+        summary: "Copies the value of a key to a new key.",
+        complexity: "O(N) worst case for collections, where N is the number of nested items. O(1) for string values.",
+        container: false,
+    },
+    Spec {
+        name: "decr",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "decrby",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "del",
+        arity: -2,
+        flags: &["write"],
+        acl_categories: &["@keyspace", "@write", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 1,
+            flags: &["RM", "delete"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Deletes one or more keys.",
+        complexity: "O(N) where N is the number of keys that will be removed. When a key to remove holds a value other than a string, the individual complexity for this key is O(M) where M is the number of elements in the list, set, sorted set or hash. Removing a single key that holds a string value is O(1).",
+        container: false,
+    },
+    Spec {
+        name: "exists",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 1,
+            flags: &["RO"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Determines whether one or more keys exist.",
+        complexity: "O(N) where N is the number of keys to check.",
+        container: false,
+    },
+    Spec {
+        name: "expire",
+        arity: -3,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Sets the expiration time of a key in seconds.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "expireat",
+        arity: -3,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "generic",
+        since: "1.2.0",
+        summary: "Sets the expiration time of a key to a Unix timestamp.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "expiretime",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "generic",
+        since: "7.0.0",
+        summary: "Returns the expiration time of a key as a Unix timestamp.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "get",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@read", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns the string value of a key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "getdel",
+        arity: 2,
+        flags: &["write", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "delete"],
+        }),
+        group: "string",
+        since: "6.2.0",
+        summary: "Returns the string value of a key after deleting the key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "getex",
+        arity: -2,
+        flags: &["write", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "6.2.0",
+        summary: "Returns the string value of a key after setting its expiration time.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "getrange",
+        arity: 4,
+        flags: &["readonly"],
+        acl_categories: &["@read", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "string",
+        since: "2.4.0",
+        summary: "Returns a substring of the string stored at a key.",
+        complexity: "O(N) where N is the length of the returned string. The complexity is ultimately determined by the returned length, but because creating a substring from an existing string is very cheap, it can be considered O(1) for small strings.",
+        container: false,
+    },
+    Spec {
+        name: "hello",
+        arity: -1,
+        flags: &[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ],
+        acl_categories: &["@fast", "@connection"],
+        keys: None,
+        group: "connection",
+        since: "6.0.0",
+        summary: "Handshakes with the Redis server.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "incr",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "incrby",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "incrbyfloat",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update"],
+        }),
+        group: "string",
+        since: "2.6.0",
+        summary: "Increment the floating point value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "keys",
+        arity: 2,
+        flags: &["readonly"],
+        acl_categories: &["@keyspace", "@read", "@slow", "@dangerous"],
+        keys: None,
+        group: "generic",
+        since: "1.0.0",
+        summary: "Returns all key names that match a pattern.",
+        complexity: "O(N) with N being the number of keys in the database, under the assumption that the key names in the database and the given pattern have limited length.",
+        container: false,
+    },
+    Spec {
+        name: "lcs",
+        arity: -3,
+        flags: &["readonly"],
+        acl_categories: &["@read", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 2,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "string",
+        since: "7.0.0",
+        summary: "Finds the longest common substring.",
+        complexity: "O(N*M) where N and M are the lengths of s1 and s2, respectively",
+        container: false,
+    },
+    Spec {
+        name: "mget",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@read", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Atomically returns the string values of one or more keys.",
+        complexity: "O(N) where N is the number of keys to retrieve.",
+        container: false,
+    },
+    Spec {
+        name: "mset",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 2,
+            flags: &["OW", "update"],
+        }),
+        group: "string",
+        since: "1.0.1",
+        summary: "Atomically creates or modifies the string values of one or more keys.",
+        complexity: "O(N) where N is the number of keys to set.",
+        container: false,
+    },
+    Spec {
+        name: "msetnx",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 2,
+            flags: &["OW", "insert"],
+        }),
+        group: "string",
+        since: "1.0.1",
+        summary: "Atomically modifies the string values of one or more keys only when all keys don't exist.",
+        complexity: "O(N) where N is the number of keys to set.",
+        container: false,
+    },
+    Spec {
+        name: "persist",
+        arity: 2,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "generic",
+        since: "2.2.0",
+        summary: "Removes the expiration time of a key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "pexpire",
+        arity: -3,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "generic",
+        since: "2.6.0",
+        summary: "Sets the expiration time of a key in milliseconds.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "pexpireat",
+        arity: -3,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "generic",
+        since: "2.6.0",
+        summary: "Sets the expiration time of a key to a Unix milliseconds timestamp.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "pexpiretime",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "generic",
+        since: "7.0.0",
+        summary: "Returns the expiration time of a key as a Unix milliseconds timestamp.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "ping",
+        arity: -1,
+        flags: &["fast"],
+        acl_categories: &["@fast", "@connection"],
+        keys: None,
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the server's liveliness response.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "psetex",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["OW", "update"],
+        }),
+        group: "string",
+        since: "2.6.0",
+        summary: "Sets both string value and expiration time in milliseconds of a key. The key is created if it doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "pttl",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "generic",
+        since: "2.6.0",
+        summary: "Returns the expiration time in milliseconds of a key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "rename",
+        arity: 3,
+        flags: &["write"],
+        acl_categories: &["@keyspace", "@write", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 2,
+            step: 1,
+            flags: &["RW"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Renames a key and overwrites the destination.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "renamenx",
+        arity: 3,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 2,
+            step: 1,
+            flags: &["RW"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Renames a key only when the target key name doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "scan",
+        arity: -2,
+        flags: &["readonly"],
+        acl_categories: &["@keyspace", "@read", "@slow"],
+        keys: None,
+        group: "generic",
+        since: "2.8.0",
+        summary: "Iterates over the key names in the database.",
+        complexity: "O(N) for every call, where N is the number of keys in the database.",
+        container: false,
+    },
+    Spec {
+        name: "set",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "access", "update", "variable_flags"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "setex",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["OW", "update"],
+        }),
+        group: "string",
+        since: "2.0.0",
+        summary: "Sets the string value and expiration time of a key. Creates the key if it doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "setnx",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        acl_categories: &["@write", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["OW", "insert"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Set the string value of a key only when the key doesn't exist.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "setrange",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        acl_categories: &["@write", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RW", "update"],
+        }),
+        group: "string",
+        since: "2.2.0",
+        summary: "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist.",
+        complexity: "O(1), not counting the time taken to copy the new string in place. Usually, this string is very small so the amortized complexity is O(1). Otherwise, complexity is O(M) with M being the length of the value argument.",
+        container: false,
+    },
+    Spec {
+        name: "strlen",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@read", "@string", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO"],
+        }),
+        group: "string",
+        since: "2.2.0",
+        summary: "Returns the length of a string value.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "substr",
+        arity: 4,
+        flags: &["readonly"],
+        acl_categories: &["@read", "@string", "@slow"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns a substring from a string value.",
+        complexity: "O(N) where N is the length of the returned string. The complexity is ultimately determined by the returned length, but because creating a substring from an existing string is very cheap, it can be considered O(1) for small strings.",
+        container: false,
+    },
+    Spec {
+        name: "touch",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 1,
+            flags: &["RO"],
+        }),
+        group: "generic",
+        since: "3.2.1",
+        summary: "Returns the number of existing keys out of those specified after updating the time they were last accessed.",
+        complexity: "O(N) where N is the number of keys that will be touched.",
+        container: false,
+    },
+    Spec {
+        name: "ttl",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO", "access"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Returns the expiration time in seconds of a key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "type",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        acl_categories: &["@keyspace", "@read", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: 1,
+            step: 1,
+            flags: &["RO"],
+        }),
+        group: "generic",
+        since: "1.0.0",
+        summary: "Determines the type of value stored at a key.",
+        complexity: "O(1)",
+        container: false,
+    },
+    Spec {
+        name: "unlink",
+        arity: -2,
+        flags: &["write", "fast"],
+        acl_categories: &["@keyspace", "@write", "@fast"],
+        keys: Some(Keys {
+            first: 1,
+            last: -1,
+            step: 1,
+            flags: &["RM", "delete"],
+        }),
+        group: "generic",
+        since: "4.0.0",
+        summary: "Asynchronously deletes one or more keys.",
+        complexity: "O(1) for each key removed regardless of its size. Then the command does O(N) work in a different thread in order to reclaim memory, where N is the number of allocations the deleted objects where composed of.",
+        container: false,
+    },
+];
+
+// The table is sorted by name, and names are lowercase.
+pub fn lookup(name: &[u8]) -> Option<&'static Spec> {
+    TABLE
+        .binary_search_by(|spec| {
+            spec.name
+                .bytes()
+                .cmp(name.iter().map(u8::to_ascii_lowercase))
+        })
+        .ok()
+        .map(|i| &TABLE[i])
+}
+
+impl Spec {
+    pub fn accepts(&self, argc: usize) -> bool {
+        if self.arity >= 0 {
+            argc as i64 == self.arity
+        } else {
+            argc as i64 >= -self.arity
+        }
+    }
+
+    // `args` includes the command name, just like key positions do.
+    pub fn keys<'a>(&self, args: &'a [Vec<u8>]) -> Vec<&'a [u8]> {
+        let Some(keys) = &self.keys else {
+            return vec![];
+        };
+        let last = if keys.last < 0 {
+            args.len() as i64 + keys.last
+        } else {
+            keys.last
+        };
+        (keys.first..=last)
+            .step_by(keys.step as usize)
+            .filter_map(|i| args.get(i as usize).map(Vec::as_slice))
+            .collect()
+    }
+
+    pub fn info(&self) -> redis::Result {
+        let (first, last, step) = self
+            .keys
+            .as_ref()
+            .map_or((0, 0, 0), |k| (k.first, k.last, k.step));
+        redis::Result::Array(vec![
+            redis::Result::BulkString(self.name.as_bytes().to_vec()),
+            redis::Result::Integer(self.arity),
+            simple_strings(self.flags),
+            redis::Result::Integer(first),
+            redis::Result::Integer(last),
+            redis::Result::Integer(step),
+            simple_strings(self.acl_categories),
+            redis::Result::Array(vec![]),
+            redis::Result::Array(self.keys.iter().map(Keys::spec).collect()),
+            redis::Result::Array(vec![]),
+        ])
+    }
+
+    pub fn docs(&self) -> redis::Result {
+        redis::Result::Map(vec![
+            (bulk_string("summary"), bulk_string(self.summary)),
+            (bulk_string("since"), bulk_string(self.since)),
+            (bulk_string("group"), bulk_string(self.group)),
+            (bulk_string("complexity"), bulk_string(self.complexity)),
+        ])
+    }
+}
+
+impl Keys {
+    fn spec(&self) -> redis::Result {
+        let last = if self.last < 0 {
+            self.last
+        } else {
+            self.last - self.first
+        };
+        redis::Result::Map(vec![
+            (bulk_string("flags"), simple_strings(self.flags)),
+            (
+                bulk_string("begin_search"),
+                redis::Result::Map(vec![
+                    (bulk_string("type"), bulk_string("index")),
+                    (
+                        bulk_string("spec"),
+                        redis::Result::Map(vec![(
+                            bulk_string("index"),
+                            redis::Result::Integer(self.first),
+                        )]),
+                    ),
+                ]),
+            ),
+            (
+                bulk_string("find_keys"),
+                redis::Result::Map(vec![
+                    (bulk_string("type"), bulk_string("range")),
+                    (
+                        bulk_string("spec"),
+                        redis::Result::Map(vec![
+                            (bulk_string("lastkey"), redis::Result::Integer(last)),
+                            (bulk_string("keystep"), redis::Result::Integer(self.step)),
+                            (bulk_string("limit"), redis::Result::Integer(0)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ])
+    }
+}
+
+fn bulk_string(s: &str) -> redis::Result {
+    redis::Result::BulkString(s.as_bytes().to_vec())
+}
+
+fn simple_strings(strings: &[&str]) -> redis::Result {
+    redis::Result::Set(
+        strings
+            .iter()
+            .map(|s| redis::Result::SimpleString(s.to_string()))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup(b"GeT").map(|s| s.name), Some("get"));
+        assert!(lookup(b"nope").is_none());
+        assert!(lookup(b"").is_none());
+        for spec in TABLE {
+            let upper = spec.name.to_ascii_uppercase();
+            assert_eq!(lookup(upper.as_bytes()).map(|s| s.name), Some(spec.name));
+        }
+    }
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(TABLE.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn test_accepts() {
+        let get = lookup(b"get").unwrap();
+        assert!(!get.accepts(1));
+        assert!(get.accepts(2));
+        assert!(!get.accepts(3));
+
+        let set = lookup(b"set").unwrap();
+        assert!(!set.accepts(2));
+        assert!(set.accepts(3));
+        assert!(set.accepts(6));
+    }
+
+    #[test]
+    fn test_keys() {
+        let args = vec![b"SET".to_vec(), b"key".to_vec(), b"value".to_vec()];
+        assert_eq!(lookup(b"set").unwrap().keys(&args), vec![&b"key"[..]]);
+        assert!(lookup(b"ping").unwrap().keys(&args).is_empty());
+    }
+}
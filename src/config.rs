@@ -0,0 +1,616 @@
+use std::path::{Path, PathBuf};
+
+use crate::{glob, redis, resp, server};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub bind: Vec<String>,
+    pub port: u16,
+    pub maxclients: usize,
+    pub maxmemory: usize,
+    pub maxmemory_policy: EvictionPolicy,
+    pub maxmemory_samples: usize,
+    pub dir: PathBuf,
+    pub hz: usize,
+    pub io_threads: usize,
+    pub proto_max_bulk_len: usize,
+    file: Option<PathBuf>,
+}
+
+// Which keys to evict once `maxmemory` is reached: any key or only those with
+// a deadline, picked by least recent use, least frequent use or at random.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionPolicy {
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    NoEviction,
+}
+
+static POLICIES: &[(&str, EvictionPolicy)] = &[
+    ("volatile-lru", EvictionPolicy::VolatileLru),
+    ("volatile-lfu", EvictionPolicy::VolatileLfu),
+    ("volatile-random", EvictionPolicy::VolatileRandom),
+    ("volatile-ttl", EvictionPolicy::VolatileTtl),
+    ("allkeys-lru", EvictionPolicy::AllKeysLru),
+    ("allkeys-lfu", EvictionPolicy::AllKeysLfu),
+    ("allkeys-random", EvictionPolicy::AllKeysRandom),
+    ("noeviction", EvictionPolicy::NoEviction),
+];
+
+impl EvictionPolicy {
+    pub fn name(&self) -> &'static str {
+        POLICIES
+            .iter()
+            .find(|(_, p)| p == self)
+            .map_or("", |(n, _)| n)
+    }
+}
+
+struct Param {
+    name: &'static str,
+    mutable: bool,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+static PARAMS: &[Param] = &[
+    Param {
+        name: "bind",
+        mutable: false,
+        get: |c| c.bind.join(" "),
+        set: |c, v| {
+            let bind = v.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+            if bind.is_empty() {
+                return Err("Too few bind addresses".to_string());
+            }
+            c.bind = bind;
+            Ok(())
+        },
+    },
+    Param {
+        name: "dir",
+        mutable: true,
+        get: |c| c.dir.to_string_lossy().into_owned(),
+        set: |c, v| {
+            c.dir = std::fs::canonicalize(v)
+                .ok()
+                .filter(|d| d.is_dir())
+                .ok_or_else(|| "No such file or directory".to_string())?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "hz",
+        mutable: true,
+        get: |c| c.hz.to_string(),
+        set: |c, v| {
+            c.hz = parse_integer(v, 1, 500)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "io-threads",
+        mutable: false,
+        get: |c| c.io_threads.to_string(),
+        set: |c, v| {
+            c.io_threads = parse_integer(v, 1, 128)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxclients",
+        mutable: true,
+        get: |c| c.maxclients.to_string(),
+        set: |c, v| {
+            c.maxclients = parse_integer(v, 1, usize::MAX)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory",
+        mutable: true,
+        get: |c| c.maxmemory.to_string(),
+        set: |c, v| {
+            c.maxmemory = parse_memory(v, 0, usize::MAX)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory-policy",
+        mutable: true,
+        get: |c| c.maxmemory_policy.name().to_string(),
+        set: |c, v| {
+            c.maxmemory_policy = POLICIES
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(v))
+                .map(|(_, p)| *p)
+                .ok_or_else(|| {
+                    let names = POLICIES.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                    format!(
+                        "argument(s) must be one of the following: {}",
+                        names.join(", ")
+                    )
+                })?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory-samples",
+        mutable: true,
+        get: |c| c.maxmemory_samples.to_string(),
+        set: |c, v| {
+            c.maxmemory_samples = parse_integer(v, 1, 64)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "port",
+        mutable: false,
+        get: |c| c.port.to_string(),
+        set: |c, v| {
+            c.port = parse_integer(v, 0, u16::MAX as usize)? as u16;
+            Ok(())
+        },
+    },
+    Param {
+        name: "proto-max-bulk-len",
+        mutable: true,
+        get: |c| c.proto_max_bulk_len.to_string(),
+        set: |c, v| {
+            c.proto_max_bulk_len = parse_memory(v, 1024 * 1024, usize::MAX)?;
+            Ok(())
+        },
+    },
+];
+
+fn param(name: &[u8]) -> Option<&'static Param> {
+    PARAMS
+        .iter()
+        .find(|p| p.name.as_bytes().eq_ignore_ascii_case(name))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: vec!["127.0.0.1".to_string()],
+            port: 6379,
+            maxclients: 10000,
+            maxmemory: 0,
+            maxmemory_policy: EvictionPolicy::NoEviction,
+            maxmemory_samples: 5,
+            dir: std::env::current_dir().unwrap_or_default(),
+            hz: 10,
+            io_threads: server::default_threads(),
+            proto_max_bulk_len: resp::Limits::default().max_bulk_len,
+            file: None,
+        }
+    }
+}
+
+impl Config {
+    // Reads a `redis.conf`-style file: one `name value...` directive per line,
+    // with `#` comments and values quoted like inline commands.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Config> {
+        let path = path.as_ref();
+        let mut config = Config {
+            file: Some(std::path::absolute(path)?),
+            ..Config::default()
+        };
+        let contents = std::fs::read_to_string(path)?;
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let directive = resp::split_args(line.as_bytes())
+                .ok_or_else(|| "Unbalanced quotes in configuration line".to_string())
+                .and_then(|args| config.apply(&args));
+            if let Err(e) = directive {
+                return Err(invalid_config(format!(
+                    "{}:{}: {} in '{}'",
+                    path.display(),
+                    n + 1,
+                    e,
+                    line
+                )));
+            }
+        }
+        Ok(config)
+    }
+
+    // Parses `rosso [config-file] [--name value...]...`: options given on the
+    // command line override the ones in the file.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> std::io::Result<Config> {
+        let mut args = args.into_iter().peekable();
+        let mut config = match args.next_if(|a| !a.starts_with("--")) {
+            Some(file) => Config::load(file)?,
+            None => Config::default(),
+        };
+        while let Some(name) = args.next() {
+            let Some(name) = name.strip_prefix("--") else {
+                return Err(invalid_config(format!("Unexpected argument '{}'", name)));
+            };
+            let mut directive = vec![name.as_bytes().to_vec()];
+            while let Some(value) = args.next_if(|a| !a.starts_with("--")) {
+                directive.push(value.into_bytes());
+            }
+            config
+                .apply(&directive)
+                .map_err(|e| invalid_config(format!("{} in '--{}'", e, name)))?;
+        }
+        Ok(config)
+    }
+
+    pub fn addrs(&self) -> std::io::Result<Vec<std::net::SocketAddr>> {
+        let mut addrs = Vec::new();
+        for bind in &self.bind {
+            addrs.extend(std::net::ToSocketAddrs::to_socket_addrs(&(
+                bind.trim_start_matches('-'),
+                self.port,
+            ))?);
+        }
+        Ok(addrs)
+    }
+
+    // Returns every parameter matching any of the glob `patterns`, once.
+    pub fn get(&self, patterns: &[&[u8]]) -> Vec<(&'static str, String)> {
+        PARAMS
+            .iter()
+            .filter(|p| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob::matches(pattern, p.name.as_bytes(), true))
+            })
+            .map(|p| (p.name, (p.get)(self)))
+            .collect()
+    }
+
+    // Sets all of `parameters` or, if any of them is invalid, none of them.
+    pub fn set(&mut self, parameters: &[(&[u8], &[u8])]) -> Result<(), redis::Error> {
+        let mut updated = self.clone();
+        for (i, (name, value)) in parameters.iter().enumerate() {
+            let name_lossy = String::from_utf8_lossy(name);
+            let param = param(name).ok_or_else(|| {
+                redis::Error::Generic(format!(
+                    "Unknown option or number of arguments for CONFIG SET - '{}'",
+                    name_lossy
+                ))
+            })?;
+            let failed = |reason: &str| {
+                redis::Error::Generic(format!(
+                    "CONFIG SET failed (possibly related to argument '{}') - {}",
+                    name_lossy, reason
+                ))
+            };
+            if !param.mutable {
+                return Err(failed("can't set immutable config"));
+            }
+            if parameters[..i]
+                .iter()
+                .any(|(other, _)| other.eq_ignore_ascii_case(name))
+            {
+                return Err(failed("duplicate parameter"));
+            }
+            let value = std::str::from_utf8(value).map_err(|_| failed("invalid argument"))?;
+            (param.set)(&mut updated, value).map_err(|e| failed(&e))?;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    // Rewrites the file the configuration was loaded from, keeping comments
+    // and unknown lines and replacing or appending every known parameter.
+    pub fn rewrite(&self) -> Result<(), redis::Error> {
+        let Some(path) = &self.file else {
+            return Err(redis::Error::Generic(
+                "The server is running without a config file".to_string(),
+            ));
+        };
+        self.rewrite_file(path)
+            .map_err(|e| redis::Error::Generic(format!("Rewriting config file: {}", e)))
+    }
+
+    fn rewrite_file(&self, path: &Path) -> std::io::Result<()> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            r => r?,
+        };
+        let default = Config::default();
+        let mut written = Vec::new();
+        let mut lines = Vec::new();
+        for line in contents.lines() {
+            let name = line.split_whitespace().next().unwrap_or_default();
+            match param(name.as_bytes()) {
+                Some(p) if !written.contains(&p.name) => {
+                    written.push(p.name);
+                    lines.push(directive(p, self));
+                }
+                Some(_) => {}
+                None => lines.push(line.to_string()),
+            }
+        }
+        for p in PARAMS {
+            if !written.contains(&p.name) && (p.get)(self) != (p.get)(&default) {
+                lines.push(directive(p, self));
+            }
+        }
+
+        let tmp = path.with_extension("rewrite.tmp");
+        std::fs::write(&tmp, lines.join("\n") + "\n")?;
+        std::fs::rename(&tmp, path)
+    }
+
+    fn apply(&mut self, directive: &[Vec<u8>]) -> Result<(), String> {
+        let (name, values) = directive.split_first().ok_or("Empty directive")?;
+        let param = param(name).ok_or("Bad directive or wrong number of arguments")?;
+        if values.is_empty() || (values.len() > 1 && param.name != "bind") {
+            return Err("wrong number of arguments".to_string());
+        }
+        let values = values
+            .iter()
+            .map(|v| std::str::from_utf8(v).map_err(|_| "invalid argument".to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        (param.set)(self, &values.join(" "))
+    }
+}
+
+fn directive(param: &Param, config: &Config) -> String {
+    let value = (param.get)(config);
+    if param.name == "bind" {
+        return format!("{} {}", param.name, value);
+    }
+    format!("{} {}", param.name, quote(&value))
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .bytes()
+            .any(|c| c.is_ascii_whitespace() || c == b'"' || c == b'\'' || c == b'\\')
+    {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn parse_integer(value: &str, min: usize, max: usize) -> Result<usize, String> {
+    let n = value
+        .parse::<usize>()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    if n < min || n > max {
+        return Err(format!(
+            "argument must be between {} and {} inclusive",
+            min, max
+        ));
+    }
+    Ok(n)
+}
+
+// Accepts sizes like `512mb` or `1gb`, with the same units as Redis.
+fn parse_memory(value: &str, min: usize, max: usize) -> Result<usize, String> {
+    let lower = value.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: usize = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    let n = digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| "argument must be a memory value".to_string())?;
+    if n < min || n > max {
+        return Err(format!(
+            "argument must be between {} and {} inclusive",
+            min, max
+        ));
+    }
+    Ok(n)
+}
+
+fn invalid_config(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(contents: &str) -> PathBuf {
+        let name: String = std::iter::repeat_with(fastrand::alphanumeric)
+            .take(16)
+            .collect();
+        let path = std::env::temp_dir().join(format!("rosso-{}.conf", name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load() {
+        let path = temp_file(
+            "# a comment\n\nport 7000\nbind 127.0.0.1 ::1\nmaxclients 10\nproto-max-bulk-len 2mb\n",
+        );
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.bind, vec!["127.0.0.1", "::1"]);
+        assert_eq!(config.maxclients, 10);
+        assert_eq!(config.proto_max_bulk_len, 2 * 1024 * 1024);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_errors() {
+        for contents in ["nope 1\n", "port 1 2\n", "port abc\n", "dir \"unbalanced\n"] {
+            let path = temp_file(contents);
+            let err = Config::load(&path).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(err.to_string().contains(":1: "), "{}", err);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_from_args() {
+        let path = temp_file("port 7000\nmaxclients 10\n");
+        let args = [
+            path.to_string_lossy().into_owned(),
+            "--port".to_string(),
+            "7001".to_string(),
+            "--bind".to_string(),
+            "0.0.0.0".to_string(),
+            "::".to_string(),
+        ];
+
+        let config = Config::from_args(args).unwrap();
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.maxclients, 10);
+        assert_eq!(config.bind, vec!["0.0.0.0", "::"]);
+
+        let err = Config::from_args(["--port".to_string()]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_get() {
+        let config = Config::default();
+
+        assert_eq!(
+            config.get(&[b"MAXCLIENTS"]),
+            vec![("maxclients", "10000".to_string())]
+        );
+        assert_eq!(
+            config.get(&[b"*max*", b"maxclients"]),
+            vec![
+                ("maxclients", "10000".to_string()),
+                ("maxmemory", "0".to_string()),
+                ("maxmemory-policy", "noeviction".to_string()),
+                ("maxmemory-samples", "5".to_string()),
+                ("proto-max-bulk-len", config.proto_max_bulk_len.to_string())
+            ]
+        );
+        assert!(config.get(&[b"nope"]).is_empty());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+
+        config.set(&[(b"maxclients", b"5")]).unwrap();
+        assert_eq!(config.maxclients, 5);
+
+        let err = config
+            .set(&[(b"maxclients", b"6"), (b"port", b"7000")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR CONFIG SET failed (possibly related to argument 'port') - can't set immutable config"
+        );
+        assert_eq!(config.maxclients, 5);
+
+        let err = config.set(&[(b"maxclients", b"abc")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR CONFIG SET failed (possibly related to argument 'maxclients') - argument couldn't be parsed into an integer"
+        );
+
+        let err = config.set(&[(b"nope", b"1")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR Unknown option or number of arguments for CONFIG SET - 'nope'"
+        );
+
+        let err = config
+            .set(&[(b"maxclients", b"1"), (b"MAXCLIENTS", b"2")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR CONFIG SET failed (possibly related to argument 'MAXCLIENTS') - duplicate parameter"
+        );
+    }
+
+    #[test]
+    fn test_set_maxmemory() {
+        let mut config = Config::default();
+
+        config
+            .set(&[
+                (b"maxmemory", b"1mb"),
+                (b"maxmemory-policy", b"ALLKEYS-LRU"),
+            ])
+            .unwrap();
+        assert_eq!(config.maxmemory, 1024 * 1024);
+        assert_eq!(config.maxmemory_policy, EvictionPolicy::AllKeysLru);
+        assert_eq!(
+            config.get(&[b"maxmemory-policy"]),
+            vec![("maxmemory-policy", "allkeys-lru".to_string())]
+        );
+
+        let err = config.set(&[(b"maxmemory-policy", b"lru")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR CONFIG SET failed (possibly related to argument 'maxmemory-policy') - argument(s) must be one of the following: volatile-lru, volatile-lfu, volatile-random, volatile-ttl, allkeys-lru, allkeys-lfu, allkeys-random, noeviction"
+        );
+        assert!(config.set(&[(b"maxmemory-samples", b"0")]).is_err());
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let path = temp_file("# keep me\nmaxclients 10\n\n# and me\nmaxclients 11\n");
+        let mut config = Config::load(&path).unwrap();
+
+        config
+            .set(&[(b"maxclients", b"20"), (b"proto-max-bulk-len", b"1mb")])
+            .unwrap();
+        config.rewrite().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# keep me\nmaxclients 20\n\n# and me\nproto-max-bulk-len 1048576\n"
+        );
+        assert_eq!(Config::load(&path).unwrap(), config);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_without_file() {
+        assert!(Config::default().rewrite().is_err());
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("with space"), "\"with space\"");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("a\"b"), "\"a\\\"b\"");
+    }
+}
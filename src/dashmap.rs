@@ -1,14 +1,35 @@
-use crate::redis;
+use std::hash::BuildHasher;
+use std::sync::atomic::Ordering::Relaxed;
+
+use crate::keyset::KeySet;
+use crate::value::Value;
+use crate::{commands, config, glob, lcs, redis, value};
+
+// Like Redis, the LFU counter of a key grows logarithmically with its
+// accesses, starts at 5 so that new keys are not evicted right away, and
+// decays by one for every minute the key is not accessed.
+const LFU_INIT_VAL: u64 = 5;
+const LFU_LOG_FACTOR: f64 = 10.0;
+const LFU_DECAY_MINUTES: u64 = 1;
 
 #[derive(Debug)]
 struct Expirable<T> {
     pub value: T,
     expires_at: Option<std::time::SystemTime>,
+    // when the key was last accessed, in milliseconds since the epoch, and
+    // its LFU counter in the lowest byte under the minute it was last updated
+    last_access: std::sync::atomic::AtomicU64,
+    frequency: std::sync::atomic::AtomicU64,
 }
 
 impl<T> Expirable<T> {
     pub fn new(value: T, expires_at: Option<std::time::SystemTime>) -> Self {
-        Expirable { value, expires_at }
+        Expirable {
+            value,
+            expires_at,
+            last_access: std::sync::atomic::AtomicU64::new(0),
+            frequency: std::sync::atomic::AtomicU64::new(LFU_INIT_VAL),
+        }
     }
 
     pub fn new_perpetual(value: T) -> Self {
@@ -18,9 +39,53 @@ impl<T> Expirable<T> {
     fn is_expired(&self, now: std::time::SystemTime) -> bool {
         self.expires_at.is_some_and(|t| t <= now)
     }
+
+    fn created(&self, now: std::time::SystemTime) {
+        let now = millis(now);
+        self.last_access.store(now, Relaxed);
+        self.frequency
+            .store((now / 60_000) << 8 | LFU_INIT_VAL, Relaxed);
+    }
+
+    fn touch(&self, now: std::time::SystemTime) {
+        let mut counter = self.frequency(now);
+        let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+        if counter < 255 && fastrand::f64() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+            counter += 1;
+        }
+        let now = millis(now);
+        self.last_access.store(now, Relaxed);
+        self.frequency.store((now / 60_000) << 8 | counter, Relaxed);
+    }
+
+    fn idle(&self, now: std::time::SystemTime) -> u64 {
+        millis(now).saturating_sub(self.last_access.load(Relaxed))
+    }
+
+    fn frequency(&self, now: std::time::SystemTime) -> u64 {
+        let frequency = self.frequency.load(Relaxed);
+        let minutes = (millis(now) / 60_000).saturating_sub(frequency >> 8);
+        (frequency & 0xff).saturating_sub(minutes / LFU_DECAY_MINUTES)
+    }
+}
+
+impl<T: Clone> Clone for Expirable<T> {
+    fn clone(&self) -> Self {
+        Expirable {
+            value: self.value.clone(),
+            expires_at: self.expires_at,
+            last_access: self.last_access.load(Relaxed).into(),
+            frequency: self.frequency.load(Relaxed).into(),
+        }
+    }
 }
 
-pub trait Clock {
+fn millis(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+pub trait Clock: Send + Sync {
     fn now(&self) -> std::time::SystemTime;
 }
 
@@ -32,23 +97,158 @@ impl Clock for StdClock {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub expired_keys: u64,
+    pub expired_time_cap_reached_count: u64,
+    pub evicted_keys: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    expired_keys: std::sync::atomic::AtomicU64,
+    expired_time_cap_reached_count: std::sync::atomic::AtomicU64,
+    evicted_keys: std::sync::atomic::AtomicU64,
+}
+
+// The active expire cycle deletes due keys in batches, checking its budget
+// in between.
+const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;
+
+type Deadline = (std::time::SystemTime, Vec<u8>);
+
+// Which keys eviction picks from at random. They are only tracked while the
+// policy needs them, so that writes do not contend on one set otherwise.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Sampling {
+    #[default]
+    Nothing,
+    AllKeys,
+    Volatile,
+}
+
+impl Sampling {
+    fn of(config: &config::Config) -> Self {
+        use config::EvictionPolicy as P;
+        match config.maxmemory_policy {
+            _ if config.maxmemory == 0 => Sampling::Nothing,
+            P::AllKeysLru | P::AllKeysLfu | P::AllKeysRandom => Sampling::AllKeys,
+            P::VolatileLru | P::VolatileLfu | P::VolatileRandom => Sampling::Volatile,
+            P::VolatileTtl | P::NoEviction => Sampling::Nothing,
+        }
+    }
+
+    fn includes(self, volatile: bool) -> bool {
+        match self {
+            Sampling::Nothing => false,
+            Sampling::AllKeys => true,
+            Sampling::Volatile => volatile,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sampled {
+    sampling: Sampling,
+    keys: KeySet,
+    // what `keys` takes, as counted in `used_memory`
+    size: usize,
+}
+
+// What an entry costs besides its key and value, roughly: the `Expirable`,
+// its slot in the table and its place in the SCAN index.
+const ENTRY_OVERHEAD: usize = 96;
+
+// What a copy of a key in an index costs besides the key, roughly.
+const INDEX_OVERHEAD: usize = 32;
+
+// SCAN returns keys in the order of their hashes, which it finds in an
+// index split by their highest bits, so that writers of different keys
+// rarely wait for each other.
+const SCAN_PARTITIONS: usize = 64;
+
+type Hashes = std::collections::BTreeSet<(u64, Vec<u8>)>;
+
+fn partition(hash: u64) -> usize {
+    (hash >> (64 - SCAN_PARTITIONS.trailing_zeros())) as usize
+}
+
+// The key is counted twice, for its copy in the SCAN index.
+fn size(key: &[u8], e: &Expirable<Value>) -> usize {
+    2 * key.len() + e.value.size() + ENTRY_OVERHEAD
+}
+
+// The map only locks one key at a time, which cannot make MSET atomic. So
+// every command also locks the stripes its keys hash to: shared for most,
+// exclusive for those that write several keys, like MSET or RENAME, so that
+// no other command sees or changes their keys halfway. Commands lock their
+// stripes in ascending order, and only ever take map entries while holding
+// them, so they cannot deadlock.
+const KEY_LOCKS: usize = 1024;
+
+fn key_locks() -> Box<[std::sync::RwLock<()>]> {
+    (0..KEY_LOCKS).map(|_| std::sync::RwLock::new(())).collect()
+}
+
+struct KeyGuards<'a> {
+    _shared: Vec<std::sync::RwLockReadGuard<'a, ()>>,
+    _exclusive: Vec<std::sync::RwLockWriteGuard<'a, ()>>,
+}
+
 pub struct Engine<'a, C = StdClock> {
-    map: dashmap::DashMap<String, Expirable<String>>,
+    map: dashmap::DashMap<Vec<u8>, Expirable<Value>>,
+    // `expires`, `sampled` and `hashes` are only updated while holding the
+    // entry of the key, so that they always agree with `map`
+    expires: std::sync::Mutex<std::collections::BTreeSet<Deadline>>,
+    sampled: std::sync::Mutex<Sampled>,
+    // whether anything is sampled, checked before locking `sampled`
+    sampling: std::sync::atomic::AtomicBool,
+    hashes: Box<[std::sync::Mutex<Hashes>]>,
+    used_memory: std::sync::atomic::AtomicUsize,
     clock: &'a C,
+    config: std::sync::RwLock<config::Config>,
+    counters: Counters,
+    locks: Box<[std::sync::RwLock<()>]>,
 }
 
 impl Engine<'_> {
     pub fn new() -> Self {
-        Engine {
+        Self::with_config(config::Config::default())
+    }
+
+    pub fn with_config(config: config::Config) -> Self {
+        let engine = Engine {
             map: dashmap::DashMap::new(),
+            expires: std::sync::Mutex::new(std::collections::BTreeSet::new()),
+            sampled: std::sync::Mutex::new(Sampled::default()),
+            sampling: std::sync::atomic::AtomicBool::new(false),
+            hashes: (0..SCAN_PARTITIONS)
+                .map(|_| std::sync::Mutex::new(Hashes::new()))
+                .collect(),
+            used_memory: std::sync::atomic::AtomicUsize::new(0),
             clock: &StdClock,
-        }
+            config: std::sync::RwLock::new(config),
+            counters: Counters::default(),
+            locks: key_locks(),
+        };
+        engine.resample();
+        engine
     }
 
     pub fn with_clock<C: Clock>(clock: &'_ C) -> Engine<'_, C> {
         Engine {
             map: dashmap::DashMap::new(),
+            expires: std::sync::Mutex::new(std::collections::BTreeSet::new()),
+            sampled: std::sync::Mutex::new(Sampled::default()),
+            sampling: std::sync::atomic::AtomicBool::new(false),
+            hashes: (0..SCAN_PARTITIONS)
+                .map(|_| std::sync::Mutex::new(Hashes::new()))
+                .collect(),
+            used_memory: std::sync::atomic::AtomicUsize::new(0),
             clock,
+            config: std::sync::RwLock::new(config::Config::default()),
+            counters: Counters::default(),
+            locks: key_locks(),
         }
     }
 }
@@ -61,89 +261,276 @@ impl Default for Engine<'_> {
 
 impl<C: Clock> redis::Engine for Engine<'_, C> {
     fn call(&self, command: redis::Command) -> redis::Result {
+        if denies_oom(&command)
+            && let Err(e) = self.evict()
+        {
+            return redis::Result::Error(e);
+        }
+        let _guards = self.lock(&command);
         match command {
-            redis::Command::Get { key: redis::Key(k) } => self
-                .get(&k)
-                .map(|e| redis::Result::BulkString(e.value.clone()))
-                .unwrap_or(redis::Result::Null),
+            redis::Command::Get { key: redis::Key(k) } => match self.get(&k) {
+                Some(e) => e.value.as_string().map_or_else(redis::Result::Error, |v| {
+                    redis::Result::BulkString(v.into_owned())
+                }),
+                None => redis::Result::Null,
+            },
+            redis::Command::GetDel { key: redis::Key(k) } => match self.entry(k) {
+                dashmap::Entry::Occupied(e) => match e.get().value.as_string() {
+                    Ok(v) => {
+                        let v = v.into_owned();
+                        self.forget(e.key(), e.get());
+                        e.remove();
+                        redis::Result::BulkString(v)
+                    }
+                    Err(err) => redis::Result::Error(err),
+                },
+                dashmap::Entry::Vacant(_) => redis::Result::Null,
+            },
+            redis::Command::GetEx {
+                key: redis::Key(k),
+                expiration,
+                persist,
+            } => match self.entry(k) {
+                dashmap::Entry::Occupied(mut e) => {
+                    let v = match e.get().value.as_string() {
+                        Ok(v) => v.into_owned(),
+                        Err(err) => return redis::Result::Error(err),
+                    };
+                    let current = e.get().expires_at;
+                    if let Some(expiration) = expiration {
+                        let Some(t) = self
+                            .expires_at(&expiration)
+                            .filter(|_| is_positive(&expiration))
+                        else {
+                            return redis::Result::Error(redis::Error::InvalidExpireTime("getex"));
+                        };
+                        if t <= self.clock.now() {
+                            self.forget(e.key(), e.get());
+                            e.remove();
+                        } else {
+                            self.reindex(e.key(), current, Some(t));
+                            e.get_mut().expires_at = Some(t);
+                        }
+                    } else if persist {
+                        self.reindex(e.key(), current, None);
+                        e.get_mut().expires_at = None;
+                    }
+                    redis::Result::BulkString(v)
+                }
+                dashmap::Entry::Vacant(_) => redis::Result::Null,
+            },
+            redis::Command::SetNx {
+                key: redis::Key(k),
+                value: redis::String(v),
+            } => match self.entry(k) {
+                dashmap::Entry::Occupied(_) => redis::Result::Integer(0),
+                e @ dashmap::Entry::Vacant(_) => {
+                    let value = Expirable::new_perpetual(Value::string(v));
+                    self.store(e, value);
+                    redis::Result::Integer(1)
+                }
+            },
+            redis::Command::MGet { keys } => redis::Result::Array(
+                keys.into_iter()
+                    .map(|redis::Key(k)| match self.get(&k) {
+                        Some(e) => e.value.as_string().map_or(redis::Result::Null, |v| {
+                            redis::Result::BulkString(v.into_owned())
+                        }),
+                        None => redis::Result::Null,
+                    })
+                    .collect(),
+            ),
+            redis::Command::MSet { pairs } => {
+                self.set_all(pairs);
+                redis::Result::Ok
+            }
+            redis::Command::MSetNx { pairs } => {
+                if pairs.iter().any(|(redis::Key(k), _)| self.get(k).is_some()) {
+                    return redis::Result::Integer(0);
+                }
+                self.set_all(pairs);
+                redis::Result::Integer(1)
+            }
             redis::Command::Set {
                 key: redis::Key(k),
                 value: redis::String(v),
                 expiration,
                 get,
                 condition,
+            } => self.set(k, v, expiration, get, condition, "set"),
+            redis::Command::SetEx {
+                key: redis::Key(k),
+                value: redis::String(v),
+                expiration,
             } => {
-                let entry = self.entry(k);
-                let ex = expiration.as_ref().and_then(|e| match e {
-                    redis::Expiration::Seconds(redis::Integer(secs)) => {
-                        Some(self.clock.now() + std::time::Duration::from_secs(*secs as u64))
-                    }
-                    redis::Expiration::Milliseconds(redis::Integer(millis)) => {
-                        Some(self.clock.now() + std::time::Duration::from_millis(*millis as u64))
-                    }
-                    redis::Expiration::UnixTimeSeconds(redis::Integer(secs)) => Some(
-                        std::time::SystemTime::UNIX_EPOCH
-                            + std::time::Duration::from_secs(*secs as u64),
-                    ),
-                    redis::Expiration::UnixTimeMilliseconds(redis::Integer(millis)) => Some(
-                        std::time::SystemTime::UNIX_EPOCH
-                            + std::time::Duration::from_millis(*millis as u64),
-                    ),
-                    _ => None,
-                });
-                match entry {
-                    dashmap::Entry::Occupied(mut e) => {
-                        if condition
-                            .as_ref()
-                            .is_some_and(|c| c == &redis::SetCondition::IfNotExists)
-                        {
-                            return redis::Result::Null;
-                        }
-                        let ex = if let Some(redis::Expiration::Keep) = expiration {
-                            e.get().expires_at
-                        } else {
-                            ex
-                        };
-                        let pv = std::mem::replace(e.get_mut(), Expirable::new(v, ex)).value;
-                        if get {
-                            redis::Result::BulkString(pv)
-                        } else {
-                            redis::Result::Ok
-                        }
-                    }
-                    dashmap::Entry::Vacant(e) => {
-                        if condition
-                            .as_ref()
-                            .is_some_and(|c| c == &redis::SetCondition::IfExists)
-                        {
-                            return redis::Result::Null;
-                        }
-                        e.insert_entry(Expirable::new(v, ex));
-                        if get {
-                            redis::Result::Null
-                        } else {
-                            redis::Result::Ok
-                        }
+                let name = match expiration {
+                    redis::Expiration::Milliseconds(_) => "psetex",
+                    _ => "setex",
+                };
+                self.set(k, v, Some(expiration), false, None, name)
+            }
+            // clients only exist within a server, which handles CLIENT itself
+            redis::Command::Client(_) => redis::Result::Error(redis::Error::Generic(
+                "CLIENT is only available to connected clients".to_string(),
+            )),
+            redis::Command::CommandCount => redis::Result::Integer(commands::TABLE.len() as i64),
+            redis::Command::CommandInfo { names } if names.is_empty() => {
+                redis::Result::Array(commands::TABLE.iter().map(commands::Spec::info).collect())
+            }
+            redis::Command::CommandInfo { names } => redis::Result::Array(
+                names
+                    .iter()
+                    .map(|redis::String(n)| {
+                        commands::lookup(n).map_or(redis::Result::Null, commands::Spec::info)
+                    })
+                    .collect(),
+            ),
+            redis::Command::CommandDocs { names } => redis::Result::Map(
+                commands::TABLE
+                    .iter()
+                    .filter(|spec| {
+                        names.is_empty()
+                            || names.iter().any(|redis::String(n)| {
+                                spec.name.as_bytes().eq_ignore_ascii_case(n)
+                            })
+                    })
+                    .map(|spec| {
+                        (
+                            redis::Result::BulkString(spec.name.as_bytes().to_vec()),
+                            spec.docs(),
+                        )
+                    })
+                    .collect(),
+            ),
+            redis::Command::CommandGetKeys { args } => {
+                let args = args
+                    .into_iter()
+                    .map(|redis::String(a)| a)
+                    .collect::<Vec<_>>();
+                match args.first().and_then(|name| commands::lookup(name)) {
+                    None => redis::Result::Error(redis::Error::Generic(
+                        "Invalid command specified".to_string(),
+                    )),
+                    Some(spec) if !spec.accepts(args.len()) => {
+                        redis::Result::Error(redis::Error::Generic(
+                            "Invalid number of arguments specified for command".to_string(),
+                        ))
                     }
+                    Some(spec) => match spec.keys(&args) {
+                        keys if keys.is_empty() => redis::Result::Error(redis::Error::Generic(
+                            "The command has no key arguments".to_string(),
+                        )),
+                        keys => redis::Result::Array(
+                            keys.into_iter()
+                                .map(|k| redis::Result::BulkString(k.to_vec()))
+                                .collect(),
+                        ),
+                    },
                 }
             }
-            redis::Command::Client => redis::Result::Ok,
-            redis::Command::Incr { key: redis::Key(k) } => match self.entry(k) {
-                dashmap::Entry::Occupied(mut e) => e
-                    .get()
-                    .value
-                    .parse()
-                    .map(|v: i64| {
-                        let nv = v + 1;
-                        e.get_mut().value = nv.to_string();
-                        redis::Result::Integer(nv)
-                    })
-                    .unwrap_or_else(|e| redis::Result::Error(e.to_string())),
-                dashmap::Entry::Vacant(e) => {
-                    e.insert_entry(Expirable::new_perpetual("1".to_string()));
-                    redis::Result::Integer(1)
+            redis::Command::ConfigGet { patterns } => {
+                let patterns = patterns
+                    .iter()
+                    .map(|redis::String(p)| p.as_slice())
+                    .collect::<Vec<_>>();
+                redis::Result::Map(
+                    self.config()
+                        .get(&patterns)
+                        .into_iter()
+                        .map(|(name, value)| {
+                            (
+                                redis::Result::BulkString(name.as_bytes().to_vec()),
+                                redis::Result::BulkString(value.into_bytes()),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            redis::Command::ConfigSet { parameters } => {
+                let parameters = parameters
+                    .iter()
+                    .map(|(redis::String(n), redis::String(v))| (n.as_slice(), v.as_slice()))
+                    .collect::<Vec<_>>();
+                let result = self.config_mut().set(&parameters);
+                match result {
+                    Ok(()) => {
+                        self.resample();
+                        // a lower `maxmemory` applies right away, as far as it can
+                        let _ = self.evict();
+                        redis::Result::Ok
+                    }
+                    Err(e) => redis::Result::Error(e),
                 }
+            }
+            redis::Command::ConfigRewrite => match self.config().rewrite() {
+                Ok(()) => redis::Result::Ok,
+                Err(e) => redis::Result::Error(e),
+            },
+            redis::Command::ConfigResetStat => {
+                self.counters.expired_keys.store(0, Relaxed);
+                self.counters
+                    .expired_time_cap_reached_count
+                    .store(0, Relaxed);
+                self.counters.evicted_keys.store(0, Relaxed);
+                redis::Result::Ok
+            }
+            redis::Command::Ping { message } => match message {
+                Some(redis::String(m)) => redis::Result::BulkString(m),
+                None => redis::Result::SimpleString("PONG".to_string()),
             },
+            redis::Command::Hello { protocol, .. } => redis::Result::Map(vec![
+                (
+                    redis::Result::BulkString(b"server".to_vec()),
+                    redis::Result::BulkString(b"rosso".to_vec()),
+                ),
+                (
+                    redis::Result::BulkString(b"version".to_vec()),
+                    redis::Result::BulkString(env!("CARGO_PKG_VERSION").as_bytes().to_vec()),
+                ),
+                (
+                    redis::Result::BulkString(b"proto".to_vec()),
+                    redis::Result::Integer(protocol.map_or(2, |redis::Integer(p)| p)),
+                ),
+                (
+                    redis::Result::BulkString(b"mode".to_vec()),
+                    redis::Result::BulkString(b"standalone".to_vec()),
+                ),
+                (
+                    redis::Result::BulkString(b"role".to_vec()),
+                    redis::Result::BulkString(b"master".to_vec()),
+                ),
+                (
+                    redis::Result::BulkString(b"modules".to_vec()),
+                    redis::Result::Array(vec![]),
+                ),
+            ]),
+            redis::Command::Incr {
+                key: redis::Key(k),
+                increment: redis::Integer(increment),
+            } => self
+                .update(k, |v| {
+                    let nv = v
+                        .map_or(Ok(0), Value::as_integer)?
+                        .checked_add(increment)
+                        .ok_or(redis::Error::Overflow)?;
+                    Ok((Value::Integer(nv), redis::Result::Integer(nv)))
+                })
+                .unwrap_or_else(redis::Result::Error),
+            redis::Command::IncrByFloat {
+                key: redis::Key(k),
+                increment,
+            } => self
+                .update(k, |v| {
+                    let nv = v.map_or(Ok(0.0), Value::as_float)? + increment;
+                    if !nv.is_finite() {
+                        return Err(redis::Error::Generic(
+                            "increment would produce NaN or Infinity".to_string(),
+                        ));
+                    }
+                    let nv = value::format_float(nv).into_bytes();
+                    Ok((Value::string(nv.clone()), redis::Result::BulkString(nv)))
+                })
+                .unwrap_or_else(redis::Result::Error),
             redis::Command::Ttl { key: redis::Key(k) } => redis::Result::Integer({
                 self.get(&k)
                     .map(|e| {
@@ -157,118 +544,1051 @@ impl<C: Clock> redis::Engine for Engine<'_, C> {
             redis::Command::Append {
                 key: redis::Key(k),
                 value: redis::String(v),
-            } => redis::Result::Integer({
+            } => match self.entry(k) {
+                dashmap::Entry::Occupied(mut e) => {
+                    let value = &mut e.get_mut().value;
+                    let size = value.size();
+                    match value.as_string_mut() {
+                        Ok(s) => {
+                            s.extend_from_slice(&v);
+                            let len = s.len();
+                            self.account(size, value.size());
+                            redis::Result::Integer(len as i64)
+                        }
+                        Err(err) => redis::Result::Error(err),
+                    }
+                }
+                dashmap::Entry::Vacant(e) => {
+                    let len = v.len();
+                    self.store(
+                        dashmap::Entry::Vacant(e),
+                        Expirable::new_perpetual(Value::string(v)),
+                    );
+                    redis::Result::Integer(len as i64)
+                }
+            },
+            redis::Command::Strlen { key: redis::Key(k) } => match self.get(&k) {
+                Some(e) => e.value.as_string().map_or_else(redis::Result::Error, |v| {
+                    redis::Result::Integer(v.len() as i64)
+                }),
+                None => redis::Result::Integer(0),
+            },
+            redis::Command::GetRange {
+                key: redis::Key(k),
+                start: redis::Integer(start),
+                end: redis::Integer(end),
+            } => match self.get(&k) {
+                Some(e) => e.value.as_string().map_or_else(redis::Result::Error, |v| {
+                    let range = range(v.len(), start, end).map_or(&[][..], |r| &v[r]);
+                    redis::Result::BulkString(range.to_vec())
+                }),
+                None => redis::Result::BulkString(vec![]),
+            },
+            redis::Command::SetRange {
+                key: redis::Key(k),
+                offset: redis::Integer(offset),
+                value: redis::String(v),
+            } => {
+                let max = self.config().proto_max_bulk_len;
+                let offset = offset as usize;
+                let too_long = offset.checked_add(v.len()).is_none_or(|len| len > max);
                 match self.entry(k) {
                     dashmap::Entry::Occupied(mut e) => {
-                        e.get_mut().value.push_str(&v);
-                        e.get().value.len() as i64
+                        let value = &mut e.get_mut().value;
+                        match value.as_string() {
+                            // nothing to write: the value is left as it is
+                            Ok(s) if v.is_empty() => return redis::Result::Integer(s.len() as i64),
+                            Ok(_) if too_long => return redis::Result::Error(string_too_long()),
+                            _ => {}
+                        }
+                        let size = value.size();
+                        let s = match value.as_string_mut() {
+                            Ok(s) => s,
+                            Err(err) => return redis::Result::Error(err),
+                        };
+                        if s.len() < offset + v.len() {
+                            s.resize(offset + v.len(), 0);
+                        }
+                        s[offset..offset + v.len()].copy_from_slice(&v);
+                        let len = s.len();
+                        self.account(size, value.size());
+                        redis::Result::Integer(len as i64)
                     }
+                    dashmap::Entry::Vacant(_) if v.is_empty() => redis::Result::Integer(0),
                     dashmap::Entry::Vacant(e) => {
-                        let len = v.len();
-                        e.insert_entry(Expirable::new_perpetual(v));
-                        len as i64
+                        if too_long {
+                            return redis::Result::Error(string_too_long());
+                        }
+                        let mut s = vec![0; offset];
+                        s.extend_from_slice(&v);
+                        let len = s.len();
+                        let value = Expirable::new_perpetual(Value::String(s));
+                        self.store(dashmap::Entry::Vacant(e), value);
+                        redis::Result::Integer(len as i64)
                     }
                 }
-            }),
-            redis::Command::Strlen { key: redis::Key(k) } => {
-                redis::Result::Integer(self.get(&k).map(|e| e.value.len() as i64).unwrap_or(0))
+            }
+            redis::Command::Lcs {
+                key1: redis::Key(k1),
+                key2: redis::Key(k2),
+                len,
+                idx,
+                min_match_len: redis::Integer(min_match_len),
+                with_match_len,
+            } => {
+                // copies each value out before looking up the other
+                let string = |k: &[u8]| match self.get(k) {
+                    Some(e) => e.value.as_string().map(std::borrow::Cow::into_owned),
+                    None => Ok(vec![]),
+                };
+                let (a, b) = match (string(&k1), string(&k2)) {
+                    (Ok(a), Ok(b)) => (a, b),
+                    _ => {
+                        return redis::Result::Error(redis::Error::Generic(
+                            "The specified keys must contain string values".to_string(),
+                        ));
+                    }
+                };
+                let table = (a.len() as u128 + 1) * (b.len() as u128 + 1) * 4;
+                if table > self.config().proto_max_bulk_len as u128 {
+                    return redis::Result::Error(redis::Error::Generic(
+                        "Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len"
+                            .to_string(),
+                    ));
+                }
+                let (lcs, matches) = lcs::lcs(&a, &b);
+                if len {
+                    return redis::Result::Integer(lcs.len() as i64);
+                }
+                if !idx {
+                    return redis::Result::BulkString(lcs);
+                }
+                let range = |(start, end): (usize, usize)| {
+                    redis::Result::Array(vec![
+                        redis::Result::Integer(start as i64),
+                        redis::Result::Integer(end as i64),
+                    ])
+                };
+                let matches = matches
+                    .into_iter()
+                    .filter(|m| m.match_len() as i64 >= min_match_len)
+                    .map(|m| {
+                        let mut reply = vec![range(m.a), range(m.b)];
+                        if with_match_len {
+                            reply.push(redis::Result::Integer(m.match_len() as i64));
+                        }
+                        redis::Result::Array(reply)
+                    })
+                    .collect();
+                redis::Result::Map(vec![
+                    (
+                        redis::Result::BulkString(b"matches".to_vec()),
+                        redis::Result::Array(matches),
+                    ),
+                    (
+                        redis::Result::BulkString(b"len".to_vec()),
+                        redis::Result::Integer(lcs.len() as i64),
+                    ),
+                ])
             }
             redis::Command::Expire {
                 key: redis::Key(k),
-                seconds: redis::Integer(s),
-            } => match self.entry(k) {
+                expiration,
+                conditions,
+            } => {
+                let Some(t) = self.expires_at(&expiration) else {
+                    return redis::Result::Error(redis::Error::InvalidExpireTime(
+                        match expiration {
+                            redis::Expiration::Milliseconds(_) => "pexpire",
+                            redis::Expiration::UnixTimeSeconds(_) => "expireat",
+                            redis::Expiration::UnixTimeMilliseconds(_) => "pexpireat",
+                            _ => "expire",
+                        },
+                    ));
+                };
+                match self.entry(k) {
+                    dashmap::Entry::Occupied(mut e) => {
+                        let current = e.get().expires_at;
+                        if !conditions.iter().all(|c| allows(c, current, t)) {
+                            return redis::Result::Integer(0);
+                        }
+                        if t <= self.clock.now() {
+                            self.forget(e.key(), e.get());
+                            e.remove();
+                        } else {
+                            self.reindex(e.key(), current, Some(t));
+                            e.get_mut().expires_at = Some(t);
+                        }
+                        redis::Result::Integer(1)
+                    }
+                    dashmap::Entry::Vacant(_) => redis::Result::Integer(0),
+                }
+            }
+            redis::Command::PTtl { key: redis::Key(k) } => redis::Result::Integer({
+                self.get(&k)
+                    .map(|e| {
+                        e.expires_at.map_or(-1, |t| {
+                            t.duration_since(self.clock.now())
+                                .map_or(-2, |d| d.as_millis() as i64)
+                        })
+                    })
+                    .unwrap_or(-2)
+            }),
+            redis::Command::ExpireTime { key: redis::Key(k) } => {
+                redis::Result::Integer(self.expire_time(&k, 1000))
+            }
+            redis::Command::PExpireTime { key: redis::Key(k) } => {
+                redis::Result::Integer(self.expire_time(&k, 1))
+            }
+            redis::Command::Persist { key: redis::Key(k) } => match self.entry(k) {
                 dashmap::Entry::Occupied(mut e) => {
-                    e.get_mut().expires_at =
-                        Some(self.clock.now() + std::time::Duration::from_secs(s as u64));
-                    redis::Result::Integer(1)
+                    let current = e.get_mut().expires_at.take();
+                    self.reindex(e.key(), current, None);
+                    redis::Result::Integer(current.is_some() as i64)
                 }
                 dashmap::Entry::Vacant(_) => redis::Result::Integer(0),
             },
+            redis::Command::Del { keys } | redis::Command::Unlink { keys } => {
+                redis::Result::Integer(
+                    keys.iter()
+                        .filter(|redis::Key(k)| self.remove(k).is_some())
+                        .count() as i64,
+                )
+            }
+            redis::Command::Exists { keys } | redis::Command::Touch { keys } => {
+                redis::Result::Integer(
+                    keys.iter()
+                        .filter(|redis::Key(k)| self.get(k).is_some())
+                        .count() as i64,
+                )
+            }
+            redis::Command::Type { key: redis::Key(k) } => redis::Result::SimpleString(
+                self.get(&k)
+                    .map_or("none", |e| e.value.type_name())
+                    .to_string(),
+            ),
+            // Both keys are locked exclusively, so no other command sees them
+            // between the removal and the insertion, or changes them.
+            redis::Command::Rename {
+                key: redis::Key(k),
+                new_key: redis::Key(nk),
+            } => match self.remove(&k) {
+                Some(e) => {
+                    self.put(self.entry(nk), e);
+                    redis::Result::Ok
+                }
+                None => redis::Result::Error(redis::Error::Generic("no such key".to_string())),
+            },
+            redis::Command::RenameNx {
+                key: redis::Key(k),
+                new_key: redis::Key(nk),
+            } => {
+                if self.get(&k).is_none() {
+                    return redis::Result::Error(redis::Error::Generic("no such key".to_string()));
+                }
+                if self.get(&nk).is_some() {
+                    return redis::Result::Integer(0);
+                }
+                match self.remove(&k) {
+                    Some(e) => {
+                        self.put(self.entry(nk), e);
+                        redis::Result::Integer(1)
+                    }
+                    None => redis::Result::Error(redis::Error::Generic("no such key".to_string())),
+                }
+            }
+            redis::Command::Keys {
+                pattern: redis::String(p),
+            } => {
+                let now = self.clock.now();
+                redis::Result::Array(
+                    self.map
+                        .iter()
+                        .filter(|e| !e.is_expired(now) && glob::matches(&p, e.key(), false))
+                        .map(|e| redis::Result::BulkString(e.key().clone()))
+                        .collect(),
+                )
+            }
+            redis::Command::Scan {
+                cursor,
+                pattern,
+                count: redis::Integer(count),
+                kind,
+            } => {
+                let (next, keys) = self.scan(cursor, count as usize);
+                let keys = keys
+                    .into_iter()
+                    .filter(|k| {
+                        pattern
+                            .as_ref()
+                            .is_none_or(|redis::String(p)| glob::matches(p, k, false))
+                    })
+                    .filter(|k| {
+                        kind.as_ref().is_none_or(|redis::String(t)| {
+                            self.map.get(k).is_some_and(|e| {
+                                t.eq_ignore_ascii_case(e.value.type_name().as_bytes())
+                            })
+                        })
+                    })
+                    .map(redis::Result::BulkString)
+                    .collect();
+                redis::Result::Array(vec![
+                    redis::Result::BulkString(next.to_string().into_bytes()),
+                    redis::Result::Array(keys),
+                ])
+            }
+            redis::Command::Copy {
+                source: redis::Key(s),
+                destination: redis::Key(d),
+                replace,
+            } => {
+                if s == d {
+                    return redis::Result::Error(redis::Error::Generic(
+                        "source and destination objects are the same".to_string(),
+                    ));
+                }
+                let Some(e) = self.get(&s).map(|e| e.clone()) else {
+                    return redis::Result::Integer(0);
+                };
+                match self.entry(d) {
+                    dashmap::Entry::Occupied(_) if !replace => redis::Result::Integer(0),
+                    entry => {
+                        self.store(entry, e);
+                        redis::Result::Integer(1)
+                    }
+                }
+            }
         }
     }
 }
 
 impl<C: Clock> Engine<'_, C> {
-    fn get(
-        &self,
-        key: &str,
-    ) -> Option<dashmap::mapref::one::Ref<'_, std::string::String, Expirable<std::string::String>>>
-    {
-        self.map
-            .remove_if(key, |_, e| e.is_expired(self.clock.now()));
-        self.map.get(key)
+    pub fn config(&self) -> std::sync::RwLockReadGuard<'_, config::Config> {
+        // the configuration is only ever replaced whole, so it cannot be
+        // observed half-updated even if a writer panicked
+        self.config.read().unwrap_or_else(|e| e.into_inner())
     }
 
-    fn entry(
-        &self,
-        key: String,
-    ) -> dashmap::Entry<'_, std::string::String, Expirable<std::string::String>> {
-        self.map
-            .remove_if(&key, |_, e| e.is_expired(self.clock.now()));
-        self.map.entry(key)
+    fn config_mut(&self) -> std::sync::RwLockWriteGuard<'_, config::Config> {
+        self.config.write().unwrap_or_else(|e| e.into_inner())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::redis::Engine;
+    pub fn stats(&self) -> Stats {
+        Stats {
+            expired_keys: self.counters.expired_keys.load(Relaxed),
+            expired_time_cap_reached_count: self
+                .counters
+                .expired_time_cap_reached_count
+                .load(Relaxed),
+            evicted_keys: self.counters.evicted_keys.load(Relaxed),
+        }
+    }
 
-    struct FakeClock {
-        now: std::cell::Cell<std::time::SystemTime>,
+    // An estimate of the memory taken by keys and values, as `maxmemory`
+    // limits it.
+    pub fn used_memory(&self) -> usize {
+        self.used_memory.load(Relaxed)
     }
 
-    impl FakeClock {
-        fn new(time: std::time::SystemTime) -> Self {
-            FakeClock {
-                now: std::cell::Cell::new(time),
+    // The earliest deadline among all keys, expired or not.
+    pub fn next_expiration(&self) -> Option<std::time::SystemTime> {
+        self.expires().first().map(|(t, _)| *t)
+    }
+
+    // Deletes the keys that are due but nobody accesses anymore, until there
+    // are none left or the cycle has run for `budget`. Returns how many keys
+    // were deleted.
+    pub fn active_expire_cycle(&self, budget: std::time::Duration) -> usize {
+        let start = self.clock.now();
+        let mut deleted = 0;
+        loop {
+            let now = self.clock.now();
+            // keys are deleted with their entry held, so not with `expires` locked
+            let due: Vec<Deadline> = self
+                .expires()
+                .iter()
+                .take_while(|(t, _)| *t <= now)
+                .take(ACTIVE_EXPIRE_KEYS_PER_LOOP)
+                .cloned()
+                .collect();
+            for (_, key) in &due {
+                if self.remove_where(key, |e| e.is_expired(now)).is_some() {
+                    deleted += 1;
+                }
+            }
+            if due.len() < ACTIVE_EXPIRE_KEYS_PER_LOOP {
+                break;
+            }
+            let elapsed = self.clock.now().duration_since(start).unwrap_or_default();
+            if elapsed >= budget {
+                self.counters
+                    .expired_time_cap_reached_count
+                    .fetch_add(1, Relaxed);
+                break;
             }
         }
+        self.counters
+            .expired_keys
+            .fetch_add(deleted as u64, Relaxed);
+        deleted
+    }
 
-        fn new_now() -> Self {
-            FakeClock::new(std::time::SystemTime::now())
-        }
+    fn expires(&self) -> std::sync::MutexGuard<'_, std::collections::BTreeSet<Deadline>> {
+        // inserting and removing keys cannot leave the index inconsistent
+        self.expires.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-        fn advance(&self, duration: std::time::Duration) {
-            self.now.set(self.now.get() + duration);
+    // The partition of the SCAN index that holds `hash`.
+    fn hashes(&self, hash: u64) -> std::sync::MutexGuard<'_, Hashes> {
+        self.hashes[partition(hash)]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sampled(&self) -> std::sync::MutexGuard<'_, Sampled> {
+        self.sampled.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Changes the deadline of an existing key in the indexes: must be called
+    // while holding its entry, so that concurrent updates of the same key are
+    // applied in order.
+    fn reindex(
+        &self,
+        key: &[u8],
+        old: Option<std::time::SystemTime>,
+        new: Option<std::time::SystemTime>,
+    ) {
+        self.move_deadline(key, old, new);
+        if old.is_some() != new.is_some() {
+            self.track(key, true, new.is_some());
         }
+    }
 
-        fn set(&self, time: std::time::SystemTime) {
-            self.now.set(time);
+    fn move_deadline(
+        &self,
+        key: &[u8],
+        old: Option<std::time::SystemTime>,
+        new: Option<std::time::SystemTime>,
+    ) {
+        if old == new {
+            return;
+        }
+        let mut expires = self.expires();
+        if let Some(t) = old {
+            expires.remove(&(t, key.to_vec()));
+            self.account(key.len() + INDEX_OVERHEAD, 0);
+        }
+        if let Some(t) = new {
+            expires.insert((t, key.to_vec()));
+            self.account(0, key.len() + INDEX_OVERHEAD);
         }
     }
 
-    impl Clock for FakeClock {
-        fn now(&self) -> std::time::SystemTime {
-            self.now.get()
+    // Adds `key` to the keys eviction samples if it exists and the policy
+    // needs it, and removes it otherwise. Must be called while holding its
+    // entry, which also orders it with `resample`.
+    fn track(&self, key: &[u8], exists: bool, volatile: bool) {
+        if !self.sampling.load(Relaxed) {
+            return;
+        }
+        let mut sampled = self.sampled();
+        let cost = 2 * key.len() + INDEX_OVERHEAD;
+        if exists && sampled.sampling.includes(volatile) {
+            if sampled.keys.insert(key) {
+                sampled.size += cost;
+                self.account(0, cost);
+            }
+        } else if sampled.keys.remove(key) {
+            sampled.size -= cost;
+            self.account(cost, 0);
         }
     }
 
-    #[test]
-    fn test_set_and_get() {
-        let redis = super::Engine::new();
+    // Starts tracking the keys the eviction policy samples from, if it now
+    // needs other ones than before.
+    fn resample(&self) {
+        let sampling = Sampling::of(&self.config());
+        {
+            let mut sampled = self.sampled();
+            if sampled.sampling == sampling {
+                return;
+            }
+            self.account(sampled.size, 0);
+            *sampled = Sampled {
+                sampling,
+                ..Sampled::default()
+            };
+            self.sampling.store(sampling != Sampling::Nothing, Relaxed);
+        }
+        if sampling == Sampling::Nothing {
+            return;
+        }
+        // Writers that missed the change above update their shard before
+        // this reaches it, and those that see it afterwards track their keys
+        // themselves.
+        for e in self.map.iter() {
+            let mut sampled = self.sampled();
+            if sampled.sampling != sampling {
+                // a later change took over
+                return;
+            }
+            if sampling.includes(e.expires_at.is_some()) && sampled.keys.insert(e.key()) {
+                let cost = 2 * e.key().len() + INDEX_OVERHEAD;
+                sampled.size += cost;
+                self.account(0, cost);
+            }
+        }
+    }
 
-        let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
-            expiration: None,
-            get: false,
-            condition: None,
-        });
-        assert_eq!(result, redis::Result::Ok);
+    // Puts `value` in `entry`, returning the value it replaces.
+    fn store(
+        &self,
+        entry: dashmap::Entry<'_, Vec<u8>, Expirable<Value>>,
+        value: Expirable<Value>,
+    ) -> Option<Expirable<Value>> {
+        value.created(self.clock.now());
+        self.put(entry, value)
+    }
 
-        let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
-        });
-        assert_eq!(result, redis::Result::BulkString("value".to_string()));
+    // Like `store`, but keeps the access history of `value`, for a key that
+    // is moved rather than written.
+    fn put(
+        &self,
+        entry: dashmap::Entry<'_, Vec<u8>, Expirable<Value>>,
+        value: Expirable<Value>,
+    ) -> Option<Expirable<Value>> {
+        let new = size(entry.key(), &value);
+        match entry {
+            dashmap::Entry::Occupied(mut o) => {
+                self.reindex(o.key(), o.get().expires_at, value.expires_at);
+                self.account(size(o.key(), o.get()), new);
+                Some(o.insert(value))
+            }
+            dashmap::Entry::Vacant(v) => {
+                self.move_deadline(v.key(), None, value.expires_at);
+                self.track(v.key(), true, value.expires_at.is_some());
+                let hash = self.map.hasher().hash_one(v.key());
+                self.hashes(hash).insert((hash, v.key().clone()));
+                self.account(0, new);
+                v.insert(value);
+                None
+            }
+        }
     }
 
-    #[test]
+    // Locks the stripes of the keys of `command`: see `KEY_LOCKS`.
+    fn lock(&self, command: &redis::Command) -> KeyGuards<'_> {
+        let (keys, exclusive) = keys(command);
+        let hasher = self.map.hasher();
+        let mut stripes: Vec<usize> = keys
+            .iter()
+            .map(|k| hasher.hash_one(k) as usize % KEY_LOCKS)
+            .collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        let mut guards = KeyGuards {
+            _shared: Vec::new(),
+            _exclusive: Vec::new(),
+        };
+        // the stripes guard no data, so a panic cannot leave them inconsistent
+        for i in stripes {
+            if exclusive {
+                let guard = self.locks[i].write().unwrap_or_else(|e| e.into_inner());
+                guards._exclusive.push(guard);
+            } else {
+                let guard = self.locks[i].read().unwrap_or_else(|e| e.into_inner());
+                guards._shared.push(guard);
+            }
+        }
+        guards
+    }
+
+    // SET and the commands like it, which name themselves in errors.
+    fn set(
+        &self,
+        k: Vec<u8>,
+        v: Vec<u8>,
+        expiration: Option<redis::Expiration>,
+        get: bool,
+        condition: Option<redis::SetCondition>,
+        name: &'static str,
+    ) -> redis::Result {
+        let entry = self.entry(k);
+        let ex = match &expiration {
+            None | Some(redis::Expiration::Keep) => None,
+            Some(e) => match self.expires_at(e).filter(|_| is_positive(e)) {
+                Some(t) => Some(t),
+                None => {
+                    return redis::Result::Error(redis::Error::InvalidExpireTime(name));
+                }
+            },
+        };
+        match entry {
+            dashmap::Entry::Occupied(e) => {
+                if condition
+                    .as_ref()
+                    .is_some_and(|c| c == &redis::SetCondition::IfNotExists)
+                {
+                    return redis::Result::Null;
+                }
+                let ex = if let Some(redis::Expiration::Keep) = expiration {
+                    e.get().expires_at
+                } else {
+                    ex
+                };
+                // SET GET leaves a key of another type untouched
+                if get && e.get().value.as_string().is_err() {
+                    return redis::Result::Error(redis::Error::WrongType);
+                }
+                let previous = if ex.is_some_and(|t| t <= self.clock.now()) {
+                    // a deadline in the past deletes the key, as in Redis
+                    self.forget(e.key(), e.get());
+                    Some(e.remove())
+                } else {
+                    let value = Expirable::new(Value::string(v), ex);
+                    self.store(dashmap::Entry::Occupied(e), value)
+                };
+                match previous {
+                    Some(pv) if get => pv
+                        .value
+                        .into_string()
+                        .map_or_else(redis::Result::Error, redis::Result::BulkString),
+                    _ => redis::Result::Ok,
+                }
+            }
+            dashmap::Entry::Vacant(e) => {
+                if condition
+                    .as_ref()
+                    .is_some_and(|c| c == &redis::SetCondition::IfExists)
+                {
+                    return redis::Result::Null;
+                }
+                if ex.is_none_or(|t| t > self.clock.now()) {
+                    let value = Expirable::new(Value::string(v), ex);
+                    self.store(dashmap::Entry::Vacant(e), value);
+                }
+                if get {
+                    redis::Result::Null
+                } else {
+                    redis::Result::Ok
+                }
+            }
+        }
+    }
+
+    // Sets every pair in order, so that the last value of a repeated key wins.
+    fn set_all(&self, pairs: Vec<(redis::Key, redis::String)>) {
+        for (redis::Key(k), redis::String(v)) in pairs {
+            let value = Expirable::new_perpetual(Value::string(v));
+            self.store(self.entry(k), value);
+        }
+    }
+
+    // Replaces the value at `key` with what `update` makes of it, or of
+    // nothing if there is no such key. An existing key keeps its TTL.
+    fn update<T>(
+        &self,
+        key: Vec<u8>,
+        update: impl FnOnce(Option<&Value>) -> Result<(Value, T), redis::Error>,
+    ) -> Result<T, redis::Error> {
+        match self.entry(key) {
+            dashmap::Entry::Occupied(mut e) => {
+                let v = &mut e.get_mut().value;
+                let (nv, result) = update(Some(v))?;
+                self.account(v.size(), nv.size());
+                *v = nv;
+                Ok(result)
+            }
+            dashmap::Entry::Vacant(e) => {
+                let (nv, result) = update(None)?;
+                self.store(dashmap::Entry::Vacant(e), Expirable::new_perpetual(nv));
+                Ok(result)
+            }
+        }
+    }
+
+    // Undoes what `store` did, for an entry about to be removed.
+    fn forget(&self, key: &[u8], e: &Expirable<Value>) {
+        self.move_deadline(key, e.expires_at, None);
+        self.track(key, false, false);
+        let hash = self.map.hasher().hash_one(key);
+        self.hashes(hash).remove(&(hash, key.to_vec()));
+        self.account(size(key, e), 0);
+    }
+
+    fn account(&self, old: usize, new: usize) {
+        if new > old {
+            self.used_memory.fetch_add(new - old, Relaxed);
+        } else {
+            self.used_memory.fetch_sub(old - new, Relaxed);
+        }
+    }
+
+    // Removes `key` if `predicate` holds for it, keeping the indexes in sync:
+    // `remove_if` checks it with the entry held.
+    fn remove_where(
+        &self,
+        key: &[u8],
+        predicate: impl FnOnce(&Expirable<Value>) -> bool,
+    ) -> Option<Expirable<Value>> {
+        self.map
+            .remove_if(key, |k, e| {
+                let remove = predicate(e);
+                if remove {
+                    self.forget(k, e);
+                }
+                remove
+            })
+            .map(|(_, e)| e)
+    }
+
+    // Evicts keys until the memory used is back within `maxmemory`, failing
+    // with OOM if the policy leaves nothing to evict.
+    fn evict(&self) -> Result<(), redis::Error> {
+        let (maxmemory, policy, samples) = {
+            let config = self.config();
+            (
+                config.maxmemory,
+                config.maxmemory_policy,
+                config.maxmemory_samples,
+            )
+        };
+        while maxmemory > 0 && self.used_memory() > maxmemory {
+            let key = self.victim(policy, samples).ok_or(redis::Error::Oom)?;
+            if self.remove_where(&key, |_| true).is_some() {
+                self.counters.evicted_keys.fetch_add(1, Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    // Like Redis, approximates LRU and LFU by evicting the best of a few keys
+    // picked at random, rather than keeping every key in order.
+    fn victim(&self, policy: config::EvictionPolicy, samples: usize) -> Option<Vec<u8>> {
+        use config::EvictionPolicy as P;
+        let picked = match policy {
+            P::NoEviction => return None,
+            P::VolatileTtl => return self.expires().first().map(|(_, k)| k.clone()),
+            P::AllKeysRandom | P::VolatileRandom => 1,
+            P::AllKeysLru | P::AllKeysLfu | P::VolatileLru | P::VolatileLfu => samples,
+        };
+        let now = self.clock.now();
+        // the keys sampled may all be deleted before they are looked up: then
+        // sample again, a few times, rather than fail the command right away
+        for _ in 0..samples * 10 {
+            let candidates = self.sampled().keys.sample(picked);
+            if candidates.is_empty() {
+                return None;
+            }
+            let victim = candidates
+                .into_iter()
+                .filter_map(|k| {
+                    let score = self.map.get(&k).map(|e| match policy {
+                        P::AllKeysLfu | P::VolatileLfu => 255 - e.frequency(now),
+                        _ => e.idle(now),
+                    })?;
+                    Some((score, k))
+                })
+                .max_by_key(|(score, _)| *score)
+                .map(|(_, k)| k);
+            if victim.is_some() {
+                return victim;
+            }
+        }
+        None
+    }
+
+    fn expired(&self) {
+        self.counters.expired_keys.fetch_add(1, Relaxed);
+    }
+
+    // Past deadlines are allowed, and are left to the caller to reject or act
+    // on; `None` means the deadline cannot be represented.
+    fn expires_at(&self, expiration: &redis::Expiration) -> Option<std::time::SystemTime> {
+        let (base, amount, millis) = match expiration {
+            redis::Expiration::Seconds(redis::Integer(secs)) => (self.clock.now(), *secs, 1000),
+            redis::Expiration::Milliseconds(redis::Integer(millis)) => {
+                (self.clock.now(), *millis, 1)
+            }
+            redis::Expiration::UnixTimeSeconds(redis::Integer(secs)) => {
+                (std::time::SystemTime::UNIX_EPOCH, *secs, 1000)
+            }
+            redis::Expiration::UnixTimeMilliseconds(redis::Integer(millis)) => {
+                (std::time::SystemTime::UNIX_EPOCH, *millis, 1)
+            }
+            redis::Expiration::Keep => return None,
+        };
+        let offset = amount.checked_mul(millis)?;
+        let duration = std::time::Duration::from_millis(offset.unsigned_abs());
+        let t = if offset < 0 {
+            base.checked_sub(duration)?
+        } else {
+            base.checked_add(duration)?
+        };
+        // like Redis, only accept deadlines that fit in i64 milliseconds
+        t.duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .map_or(true, |d| d.as_millis() <= i64::MAX as u128)
+            .then_some(t)
+    }
+
+    // EXPIRETIME and PEXPIRETIME, in units of `millis` milliseconds.
+    fn expire_time(&self, key: &[u8], millis: i64) -> i64 {
+        self.get(key).map_or(-2, |e| {
+            e.expires_at.map_or(-1, |t| {
+                t.duration_since(std::time::SystemTime::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as i64 / millis)
+            })
+        })
+    }
+
+    fn get(&self, key: &[u8]) -> Option<dashmap::mapref::one::Ref<'_, Vec<u8>, Expirable<Value>>> {
+        if self
+            .remove_where(key, |e| e.is_expired(self.clock.now()))
+            .is_some()
+        {
+            self.expired();
+        }
+        let e = self.map.get(key)?;
+        e.touch(self.clock.now());
+        Some(e)
+    }
+
+    // SCAN walks the keys in the order of their hashes, which unlike their
+    // position in the table do not change when the map is resized: a cursor is
+    // the lowest hash still to be returned, so every key that is present for
+    // the whole iteration is returned exactly once. Returns the next cursor,
+    // 0 once the iteration is over, and the keys from `cursor` up to it.
+    fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Vec<u8>>) {
+        let mut batch: Vec<(u64, Vec<u8>)> = Vec::with_capacity(count.min(1024));
+        let mut next = 0;
+        'partitions: for i in partition(cursor)..SCAN_PARTITIONS {
+            let hashes = self.hashes[i].lock().unwrap_or_else(|e| e.into_inner());
+            for (hash, key) in hashes.range((cursor, Vec::new())..) {
+                // keys sharing the last hash all go in this batch, even beyond `count`
+                if let Some(&(last, _)) = batch.last()
+                    && batch.len() >= count
+                    && *hash != last
+                {
+                    next = last + 1;
+                    break 'partitions;
+                }
+                batch.push((*hash, key.clone()));
+            }
+        }
+        // keys are looked up without holding the index, like writers do
+        let now = self.clock.now();
+        let keys = batch
+            .into_iter()
+            .map(|(_, k)| k)
+            .filter(|k| self.map.get(k).is_some_and(|e| !e.is_expired(now)))
+            .collect();
+        (next, keys)
+    }
+
+    // Removes a key, returning it only if it had not expired yet.
+    fn remove(&self, key: &[u8]) -> Option<Expirable<Value>> {
+        let e = self.remove_where(key, |_| true)?;
+        if e.is_expired(self.clock.now()) {
+            self.expired();
+            return None;
+        }
+        Some(e)
+    }
+
+    fn entry(&self, key: Vec<u8>) -> dashmap::Entry<'_, Vec<u8>, Expirable<Value>> {
+        if self
+            .remove_where(&key, |e| e.is_expired(self.clock.now()))
+            .is_some()
+        {
+            self.expired();
+        }
+        let entry = self.map.entry(key);
+        if let dashmap::Entry::Occupied(e) = &entry {
+            e.get().touch(self.clock.now());
+        }
+        entry
+    }
+}
+
+// The commands that may need more memory, and fail once `maxmemory` is
+// reached and nothing can be evicted.
+fn denies_oom(command: &redis::Command) -> bool {
+    matches!(
+        command,
+        redis::Command::Set { .. }
+            | redis::Command::SetEx { .. }
+            | redis::Command::Incr { .. }
+            | redis::Command::IncrByFloat { .. }
+            | redis::Command::Append { .. }
+            | redis::Command::Copy { .. }
+            | redis::Command::MSet { .. }
+            | redis::Command::MSetNx { .. }
+            | redis::Command::SetRange { .. }
+            | redis::Command::SetNx { .. }
+    )
+}
+
+// The bytes GETRANGE returns out of a string of `len` bytes: negative
+// indexes count from the end, and the range is clamped to the string.
+fn range(len: usize, start: i64, end: i64) -> Option<std::ops::RangeInclusive<usize>> {
+    if start < 0 && end < 0 && start > end {
+        return None;
+    }
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let end = if end < 0 { len + end } else { end }.max(0).min(len - 1);
+    if start > end || len == 0 {
+        return None;
+    }
+    Some(start as usize..=end as usize)
+}
+
+fn string_too_long() -> redis::Error {
+    redis::Error::Generic("string exceeds maximum allowed size (proto-max-bulk-len)".to_string())
+}
+
+// The keys of `command`, and whether it needs them to itself.
+fn keys(command: &redis::Command) -> (Vec<&[u8]>, bool) {
+    use redis::Command as C;
+    let keys = match command {
+        C::MSet { pairs } | C::MSetNx { pairs } => {
+            return (pairs.iter().map(|(k, _)| k.0.as_slice()).collect(), true);
+        }
+        C::Rename { key, new_key } | C::RenameNx { key, new_key } => {
+            return (vec![&key.0, &new_key.0], true);
+        }
+        C::Copy {
+            source,
+            destination,
+            ..
+        } => return (vec![&source.0, &destination.0], true),
+        C::Get { key }
+        | C::GetDel { key }
+        | C::GetEx { key, .. }
+        | C::SetNx { key, .. }
+        | C::SetEx { key, .. }
+        | C::Set { key, .. }
+        | C::Incr { key, .. }
+        | C::IncrByFloat { key, .. }
+        | C::Ttl { key }
+        | C::PTtl { key }
+        | C::ExpireTime { key }
+        | C::PExpireTime { key }
+        | C::Persist { key }
+        | C::Append { key, .. }
+        | C::Strlen { key }
+        | C::GetRange { key, .. }
+        | C::SetRange { key, .. }
+        | C::Expire { key, .. }
+        | C::Type { key } => vec![key],
+        C::MGet { keys }
+        | C::Del { keys }
+        | C::Unlink { keys }
+        | C::Exists { keys }
+        | C::Touch { keys } => keys.iter().collect(),
+        C::Lcs { key1, key2, .. } => vec![key1, key2],
+        C::Client(_)
+        | C::CommandCount
+        | C::CommandInfo { .. }
+        | C::CommandDocs { .. }
+        | C::CommandGetKeys { .. }
+        | C::ConfigGet { .. }
+        | C::ConfigSet { .. }
+        | C::ConfigRewrite
+        | C::ConfigResetStat
+        | C::Ping { .. }
+        | C::Hello { .. }
+        | C::Keys { .. }
+        | C::Scan { .. } => vec![],
+    };
+    (keys.into_iter().map(|k| k.0.as_slice()).collect(), false)
+}
+
+// Like Redis, SET and GETEX only take positive expirations.
+fn is_positive(expiration: &redis::Expiration) -> bool {
+    match expiration {
+        redis::Expiration::Seconds(redis::Integer(n))
+        | redis::Expiration::Milliseconds(redis::Integer(n))
+        | redis::Expiration::UnixTimeSeconds(redis::Integer(n))
+        | redis::Expiration::UnixTimeMilliseconds(redis::Integer(n)) => *n > 0,
+        redis::Expiration::Keep => false,
+    }
+}
+
+// Keys without a deadline count as never expiring for GT and LT.
+fn allows(
+    condition: &redis::ExpireCondition,
+    current: Option<std::time::SystemTime>,
+    new: std::time::SystemTime,
+) -> bool {
+    match condition {
+        redis::ExpireCondition::IfNoExpiry => current.is_none(),
+        redis::ExpireCondition::IfExpiry => current.is_some(),
+        redis::ExpireCondition::IfGreater => current.is_some_and(|c| new > c),
+        redis::ExpireCondition::IfLess => current.is_none_or(|c| new < c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis::Engine;
+
+    struct FakeClock {
+        now: std::sync::Mutex<std::time::SystemTime>,
+    }
+
+    impl FakeClock {
+        fn new(time: std::time::SystemTime) -> Self {
+            FakeClock {
+                now: std::sync::Mutex::new(time),
+            }
+        }
+
+        fn new_now() -> Self {
+            FakeClock::new(std::time::SystemTime::now())
+        }
+
+        fn advance(&self, duration: std::time::Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+
+        fn set(&self, time: std::time::SystemTime) {
+            *self.now.lock().unwrap() = time;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> std::time::SystemTime {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
+            expiration: None,
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Ok);
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"value".to_vec()));
+    }
+
+    #[test]
     fn test_get_nonexistent_key() {
         let redis = super::Engine::new();
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("nonexistent".to_string()),
+            key: redis::Key(b"nonexistent".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -279,8 +1599,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
             get: false,
             condition: None,
@@ -290,7 +1610,7 @@ mod tests {
         clock.advance(std::time::Duration::from_secs(1));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -301,8 +1621,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::Milliseconds(redis::Integer(500))),
             get: false,
             condition: None,
@@ -312,7 +1632,7 @@ mod tests {
         clock.advance(std::time::Duration::from_millis(500));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -323,8 +1643,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(
                 1749371595,
             ))),
@@ -336,7 +1656,7 @@ mod tests {
         clock.set(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1749371596));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -347,8 +1667,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::UnixTimeMilliseconds(redis::Integer(
                 1749371595123,
             ))),
@@ -361,19 +1681,96 @@ mod tests {
             .set(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1749371595124));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
 
+    #[test]
+    fn test_set_invalid_expiration() {
+        let redis = super::Engine::new();
+
+        for expiration in [
+            redis::Expiration::Seconds(redis::Integer(-1)),
+            redis::Expiration::Seconds(redis::Integer(0)),
+            redis::Expiration::Milliseconds(redis::Integer(0)),
+            redis::Expiration::UnixTimeSeconds(redis::Integer(0)),
+            redis::Expiration::Seconds(redis::Integer(i64::MAX)),
+            redis::Expiration::UnixTimeMilliseconds(redis::Integer(-1)),
+        ] {
+            let result = redis.call(redis::Command::Set {
+                key: redis::Key(b"key".to_vec()),
+                value: redis::String(b"value".to_vec()),
+                expiration: Some(expiration),
+                get: false,
+                condition: None,
+            });
+            assert_eq!(
+                result,
+                redis::Result::Error(redis::Error::InvalidExpireTime("set"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_setex_overflow() {
+        let redis = super::Engine::new();
+        for (expiration, name) in [
+            (
+                redis::Expiration::Seconds(redis::Integer(i64::MAX)),
+                "setex",
+            ),
+            (
+                redis::Expiration::Milliseconds(redis::Integer(i64::MAX)),
+                "psetex",
+            ),
+        ] {
+            let result = redis.call(redis::Command::SetEx {
+                key: redis::Key(b"key".to_vec()),
+                value: redis::String(b"value".to_vec()),
+                expiration,
+            });
+            assert_eq!(
+                result,
+                redis::Result::Error(redis::Error::InvalidExpireTime(name))
+            );
+        }
+        assert!(redis.map.is_empty());
+    }
+
+    #[test]
+    fn test_set_expiration_in_the_past() {
+        let redis = super::Engine::new();
+        let set_past = |key: &[u8], get| {
+            redis.call(redis::Command::Set {
+                key: redis::Key(key.to_vec()),
+                value: redis::String(b"new_value".to_vec()),
+                expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
+                get,
+                condition: None,
+            })
+        };
+
+        set(&redis, b"key", b"value", None);
+        assert_eq!(
+            set_past(b"key", true),
+            redis::Result::BulkString(b"value".to_vec())
+        );
+        assert!(!redis.map.contains_key(b"key".as_slice()));
+        assert_eq!(set_past(b"missing", false), redis::Result::Ok);
+        assert!(!redis.map.contains_key(b"missing".as_slice()));
+        assert!(indexed(&redis).is_empty());
+        assert_eq!(redis.used_memory(), 0);
+    }
+
     #[test]
     fn test_set_expiration_keep() {
         let clock = FakeClock::new_now();
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
             get: false,
             condition: None,
@@ -381,8 +1778,8 @@ mod tests {
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::Keep),
             get: false,
             condition: None,
@@ -392,7 +1789,7 @@ mod tests {
         clock.advance(std::time::Duration::from_secs(1));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -403,8 +1800,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
             get: false,
             condition: None,
@@ -412,8 +1809,8 @@ mod tests {
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: None,
             get: false,
             condition: None,
@@ -423,9 +1820,9 @@ mod tests {
         clock.advance(std::time::Duration::from_secs(1));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("value".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"value".to_vec()));
     }
 
     #[test]
@@ -433,24 +1830,24 @@ mod tests {
         let redis = super::Engine::new();
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: None,
             get: true,
             condition: None,
         });
         assert_eq!(result, redis::Result::Null);
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("new_value".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"new_value".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: true,
             condition: None,
         });
-        assert_eq!(result, redis::Result::BulkString("value".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"value".to_vec()));
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("newer_value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"newer_value".to_vec()),
             expiration: None,
             get: true,
             condition: None,
@@ -464,8 +1861,8 @@ mod tests {
 
         // key does not exist
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: None,
             get: false,
             condition: Some(redis::SetCondition::IfNotExists),
@@ -474,39 +1871,39 @@ mod tests {
 
         // key exists
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("new_value".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"new_value".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: false,
             condition: Some(redis::SetCondition::IfNotExists),
         });
         assert_eq!(result, redis::Result::Null);
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("value".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"value".to_vec()));
 
         // key exists, but it's expired
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("new_value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"new_value".to_vec()),
             expiration: None,
             get: false,
             condition: Some(redis::SetCondition::IfNotExists),
         });
         assert_eq!(result, redis::Result::Ok);
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("new_value".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"new_value".to_vec()));
     }
 
     #[test]
@@ -515,59 +1912,59 @@ mod tests {
 
         // key does not exist
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: None,
             get: false,
             condition: Some(redis::SetCondition::IfExists),
         });
         assert_eq!(result, redis::Result::Null);
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
 
         // key exists
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
             expiration: None,
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("new_value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"new_value".to_vec()),
             expiration: None,
             get: false,
             condition: Some(redis::SetCondition::IfExists),
         });
         assert_eq!(result, redis::Result::Ok);
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("new_value".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"new_value".to_vec()));
 
         // key exists, but it's expired
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("value".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"value".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("new_value".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"new_value".to_vec()),
             expiration: None,
             get: false,
             condition: Some(redis::SetCondition::IfExists),
         });
         assert_eq!(result, redis::Result::Null);
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Null);
     }
@@ -576,8 +1973,154 @@ mod tests {
     fn test_client() {
         let redis = super::Engine::new();
 
-        let result = redis.call(redis::Command::Client);
+        let result = redis.call(redis::Command::Client(redis::ClientCommand::Id));
+        assert!(matches!(result, redis::Result::Error(_)));
+    }
+
+    #[test]
+    fn test_command_count() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::CommandCount);
+        assert_eq!(result, redis::Result::Integer(commands::TABLE.len() as i64));
+    }
+
+    #[test]
+    fn test_command_info() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::CommandInfo {
+            names: vec![
+                redis::String(b"GET".to_vec()),
+                redis::String(b"nope".to_vec()),
+            ],
+        });
+        let redis::Result::Array(infos) = result else {
+            panic!("expected an array, got {:?}", result);
+        };
+        let redis::Result::Array(get) = &infos[0] else {
+            panic!("expected an array, got {:?}", infos[0]);
+        };
+        assert_eq!(get[0], redis::Result::BulkString(b"get".to_vec()));
+        assert_eq!(get[1], redis::Result::Integer(2));
+        assert_eq!(infos[1], redis::Result::Null);
+    }
+
+    #[test]
+    fn test_command_getkeys() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::CommandGetKeys {
+            args: vec![
+                redis::String(b"set".to_vec()),
+                redis::String(b"key".to_vec()),
+                redis::String(b"value".to_vec()),
+            ],
+        });
+        assert_eq!(
+            result,
+            redis::Result::Array(vec![redis::Result::BulkString(b"key".to_vec())])
+        );
+
+        let result = redis.call(redis::Command::CommandGetKeys {
+            args: vec![redis::String(b"ping".to_vec())],
+        });
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic(
+                "The command has no key arguments".to_string()
+            ))
+        );
+
+        let result = redis.call(redis::Command::CommandGetKeys {
+            args: vec![redis::String(b"get".to_vec())],
+        });
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic(
+                "Invalid number of arguments specified for command".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_config_get_and_set() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::ConfigSet {
+            parameters: vec![(
+                redis::String(b"maxclients".to_vec()),
+                redis::String(b"42".to_vec()),
+            )],
+        });
         assert_eq!(result, redis::Result::Ok);
+        assert_eq!(redis.config().maxclients, 42);
+
+        let result = redis.call(redis::Command::ConfigGet {
+            patterns: vec![redis::String(b"maxcl*".to_vec())],
+        });
+        assert_eq!(
+            result,
+            redis::Result::Map(vec![(
+                redis::Result::BulkString(b"maxclients".to_vec()),
+                redis::Result::BulkString(b"42".to_vec())
+            )])
+        );
+
+        let result = redis.call(redis::Command::ConfigSet {
+            parameters: vec![(
+                redis::String(b"port".to_vec()),
+                redis::String(b"1".to_vec()),
+            )],
+        });
+        assert!(matches!(
+            result,
+            redis::Result::Error(redis::Error::Generic(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_rewrite_without_file() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::ConfigRewrite);
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic(
+                "The server is running without a config file".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ping() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::Ping { message: None });
+        assert_eq!(result, redis::Result::SimpleString("PONG".to_string()));
+
+        let result = redis.call(redis::Command::Ping {
+            message: Some(redis::String(b"hello".to_vec())),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn test_hello() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::Hello {
+            protocol: Some(redis::Integer(3)),
+            auth: None,
+            setname: None,
+        });
+        let redis::Result::Map(fields) = result else {
+            panic!("expected a map, got {:?}", result);
+        };
+        assert!(fields.contains(&(
+            redis::Result::BulkString(b"proto".to_vec()),
+            redis::Result::Integer(3),
+        )));
     }
 
     #[test]
@@ -586,135 +2129,556 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Incr {
-            key: redis::Key("counter".to_string()),
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
         });
         assert_eq!(result, redis::Result::Integer(1));
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("counter".to_string()),
-            value: redis::String("42".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"counter".to_vec()),
+            value: redis::String(b"42".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Incr {
-            key: redis::Key("counter".to_string()),
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
         });
         assert_eq!(result, redis::Result::Integer(1));
 
         let result = redis.call(redis::Command::Incr {
-            key: redis::Key("counter".to_string()),
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
         });
         assert_eq!(result, redis::Result::Integer(2));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("counter".to_string()),
+            key: redis::Key(b"counter".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("2".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"2".to_vec()));
     }
 
     #[test]
-    fn test_ttl() {
-        let clock = FakeClock::new_now();
-        let redis = super::Engine::with_clock(&clock);
+    fn test_incr_overflow() {
+        let redis = super::Engine::new();
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("foo".to_string()),
-            value: redis::String("42".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
+            key: redis::Key(b"counter".to_vec()),
+            value: redis::String(i64::MAX.to_string().into_bytes()),
+            expiration: None,
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
 
-        let ttl = redis.call(redis::Command::Ttl {
-            key: redis::Key("foo".to_string()),
-        });
-        assert_eq!(ttl, redis::Result::Integer(1));
-
-        clock.advance(std::time::Duration::from_millis(500));
-        let ttl = redis.call(redis::Command::Ttl {
-            key: redis::Key("foo".to_string()),
-        });
-        assert_eq!(ttl, redis::Result::Integer(0));
-
-        clock.advance(std::time::Duration::from_millis(500));
-        let ttl = redis.call(redis::Command::Ttl {
-            key: redis::Key("foo".to_string()),
+        let result = redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
         });
-        assert_eq!(ttl, redis::Result::Integer(-2));
+        assert_eq!(result, redis::Result::Error(redis::Error::Overflow));
     }
 
     #[test]
-    fn test_no_ttl() {
-        let clock = FakeClock::new_now();
-        let redis = super::Engine::with_clock(&clock);
+    fn test_incr_not_an_integer() {
+        let redis = super::Engine::new();
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("foo".to_string()),
-            value: redis::String("42".to_string()),
+            key: redis::Key(b"counter".to_vec()),
+            value: redis::String(b"forty-two".to_vec()),
             expiration: None,
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
 
-        let ttl = redis.call(redis::Command::Ttl {
-            key: redis::Key("foo".to_string()),
+        let result = redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
         });
-        assert_eq!(ttl, redis::Result::Integer(-1));
+        assert_eq!(result, redis::Result::Error(redis::Error::NotAnInteger));
     }
 
     #[test]
-    fn test_append() {
+    fn test_incr_by() {
         let redis = super::Engine::new();
-
-        let result = redis.call(redis::Command::Append {
-            key: redis::Key("key".to_string()),
-            value: redis::String("hello".to_string()),
-        });
-        assert_eq!(result, redis::Result::Integer(5));
-
-        let result = redis.call(redis::Command::Append {
-            key: redis::Key("key".to_string()),
-            value: redis::String(", world!".to_string()),
-        });
-        assert_eq!(result, redis::Result::Integer(13));
-
+        let incr = |increment| {
+            redis.call(redis::Command::Incr {
+                key: redis::Key(b"counter".to_vec()),
+                increment: redis::Integer(increment),
+            })
+        };
+
+        assert_eq!(incr(-5), redis::Result::Integer(-5));
+        assert_eq!(incr(15), redis::Result::Integer(10));
+        assert_eq!(incr(i64::MIN), redis::Result::Integer(i64::MIN + 10));
+        assert_eq!(incr(-11), redis::Result::Error(redis::Error::Overflow));
+        // a failed increment leaves the value as it was
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"counter".to_vec()),
         });
         assert_eq!(
             result,
-            redis::Result::BulkString("hello, world!".to_string())
+            redis::Result::BulkString((i64::MIN + 10).to_string().into_bytes())
         );
     }
 
+    #[test]
+    fn test_incr_by_float() {
+        let redis = super::Engine::new();
+        let incr = |increment| {
+            redis.call(redis::Command::IncrByFloat {
+                key: redis::Key(b"counter".to_vec()),
+                increment,
+            })
+        };
+
+        assert_eq!(incr(10.5), redis::Result::BulkString(b"10.5".to_vec()));
+        assert_eq!(incr(0.1), redis::Result::BulkString(b"10.6".to_vec()));
+        assert_eq!(incr(-5.6), redis::Result::BulkString(b"5".to_vec()));
+        // the result is an integer again
+        let result = redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
+        });
+        assert_eq!(result, redis::Result::Integer(6));
+
+        assert_eq!(
+            incr(f64::MAX),
+            redis::Result::BulkString(value::format_float(f64::MAX).into_bytes())
+        );
+        assert_eq!(
+            incr(f64::MAX),
+            redis::Result::Error(redis::Error::Generic(
+                "increment would produce NaN or Infinity".to_string()
+            ))
+        );
+
+        set(&redis, b"counter", b"1e3", None);
+        assert_eq!(incr(1.0), redis::Result::BulkString(b"1001".to_vec()));
+        set(&redis, b"counter", b"abc", None);
+        assert_eq!(incr(1.0), redis::Result::Error(redis::Error::NotAFloat));
+
+        // too small for 17 fractional digits
+        redis.call(redis::Command::Del {
+            keys: keys(&[b"counter"]),
+        });
+        assert_eq!(incr(1e-20), redis::Result::BulkString(b"0".to_vec()));
+    }
+
+    #[test]
+    fn test_incr_keeps_ttl() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(
+            &redis,
+            b"counter",
+            b"1",
+            Some(redis::Expiration::Seconds(redis::Integer(10))),
+        );
+        redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(2),
+        });
+        redis.call(redis::Command::IncrByFloat {
+            key: redis::Key(b"counter".to_vec()),
+            increment: 0.5,
+        });
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"counter".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"3.5".to_vec()));
+        let result = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"counter".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(10));
+    }
+
+    #[test]
+    fn test_integer_encoding() {
+        let redis = super::Engine::new();
+        let value = || redis.map.get(b"counter".as_slice()).unwrap().value.clone();
+        set(&redis, b"counter", b"10", None);
+        assert_eq!(value(), Value::Integer(10));
+        redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(-20),
+        });
+        assert_eq!(value(), Value::Integer(-10));
+
+        let result = redis.call(redis::Command::Append {
+            key: redis::Key(b"counter".to_vec()),
+            value: redis::String(b"0".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(4));
+        assert_eq!(value(), Value::String(b"-100".to_vec()));
+        let result = redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
+        });
+        assert_eq!(result, redis::Result::Integer(-99));
+        assert_eq!(value(), Value::Integer(-99));
+
+        // strings that do not spell integers the canonical way stay strings
+        set(&redis, b"counter", b"007", None);
+        assert_eq!(value(), Value::String(b"007".to_vec()));
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"counter".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"007".to_vec()));
+    }
+
+    #[test]
+    fn test_getdel() {
+        let redis = super::Engine::new();
+        set(
+            &redis,
+            b"token",
+            b"v",
+            Some(redis::Expiration::Seconds(redis::Integer(10))),
+        );
+        let getdel = || {
+            redis.call(redis::Command::GetDel {
+                key: redis::Key(b"token".to_vec()),
+            })
+        };
+
+        assert_eq!(getdel(), redis::Result::BulkString(b"v".to_vec()));
+        assert_eq!(getdel(), redis::Result::Null);
+        assert_eq!(redis.used_memory(), 0);
+        assert!(indexed(&redis).is_empty());
+
+        redis.store(
+            redis.map.entry(b"token".to_vec()),
+            Expirable::new_perpetual(Value::List(std::collections::VecDeque::new())),
+        );
+        assert_eq!(getdel(), redis::Result::Error(redis::Error::WrongType));
+        assert!(redis.map.contains_key(b"token".as_slice()));
+    }
+
+    #[test]
+    fn test_getex() {
+        let clock = FakeClock::new(std::time::SystemTime::UNIX_EPOCH);
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"token", b"v", None);
+        let getex = |expiration, persist| {
+            redis.call(redis::Command::GetEx {
+                key: redis::Key(b"token".to_vec()),
+                expiration,
+                persist,
+            })
+        };
+        let value = redis::Result::BulkString(b"v".to_vec());
+
+        assert_eq!(getex(None, false), value);
+        assert!(indexed(&redis).is_empty());
+        let ex = redis::Expiration::Seconds(redis::Integer(10));
+        assert_eq!(getex(Some(ex), false), value);
+        assert_eq!(indexed(&redis), vec![(10, b"token".to_vec())]);
+        let pxat = redis::Expiration::UnixTimeMilliseconds(redis::Integer(20_000));
+        assert_eq!(getex(Some(pxat), false), value);
+        assert_eq!(indexed(&redis), vec![(20, b"token".to_vec())]);
+        assert_eq!(getex(None, false), value);
+        assert_eq!(indexed(&redis), vec![(20, b"token".to_vec())]);
+        assert_eq!(getex(None, true), value);
+        assert!(indexed(&redis).is_empty());
+
+        let zero = redis::Expiration::Seconds(redis::Integer(0));
+        assert_eq!(
+            getex(Some(zero), false),
+            redis::Result::Error(redis::Error::InvalidExpireTime("getex"))
+        );
+        // a deadline in the past deletes the key, after replying its value
+        clock.advance(std::time::Duration::from_secs(100));
+        let exat = redis::Expiration::UnixTimeSeconds(redis::Integer(50));
+        assert_eq!(getex(Some(exat), false), value);
+        assert_eq!(getex(None, false), redis::Result::Null);
+        assert_eq!(redis.used_memory(), 0);
+    }
+
+    #[test]
+    fn test_setnx() {
+        let redis = super::Engine::new();
+        let setnx = |value: &[u8]| {
+            redis.call(redis::Command::SetNx {
+                key: redis::Key(b"key".to_vec()),
+                value: redis::String(value.to_vec()),
+            })
+        };
+
+        assert_eq!(setnx(b"1"), redis::Result::Integer(1));
+        assert_eq!(setnx(b"2"), redis::Result::Integer(0));
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"1".to_vec()));
+    }
+
+    #[test]
+    fn test_getrange() {
+        let redis = super::Engine::new();
+        set(&redis, b"key", b"This is a string", None);
+        let getrange = |key: &[u8], start, end| {
+            redis.call(redis::Command::GetRange {
+                key: redis::Key(key.to_vec()),
+                start: redis::Integer(start),
+                end: redis::Integer(end),
+            })
+        };
+        let bulk = |s: &[u8]| redis::Result::BulkString(s.to_vec());
+
+        assert_eq!(getrange(b"key", 0, 3), bulk(b"This"));
+        assert_eq!(getrange(b"key", -3, -1), bulk(b"ing"));
+        assert_eq!(getrange(b"key", 0, -1), bulk(b"This is a string"));
+        assert_eq!(getrange(b"key", 10, 100), bulk(b"string"));
+        assert_eq!(getrange(b"key", -100, 3), bulk(b"This"));
+        assert_eq!(getrange(b"key", 5, 3), bulk(b""));
+        assert_eq!(getrange(b"key", -1, -5), bulk(b""));
+        assert_eq!(getrange(b"key", 100, 200), bulk(b""));
+        assert_eq!(getrange(b"missing", 0, -1), bulk(b""));
+
+        set(&redis, b"counter", b"12345", None);
+        assert_eq!(getrange(b"counter", 1, 2), bulk(b"23"));
+    }
+
+    #[test]
+    fn test_setrange() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(
+            &redis,
+            b"key",
+            b"Hello World",
+            Some(redis::Expiration::Seconds(redis::Integer(10))),
+        );
+        let setrange = |key: &[u8], offset, value: &[u8]| {
+            redis.call(redis::Command::SetRange {
+                key: redis::Key(key.to_vec()),
+                offset: redis::Integer(offset),
+                value: redis::String(value.to_vec()),
+            })
+        };
+        let get = |key: &[u8]| {
+            redis.call(redis::Command::Get {
+                key: redis::Key(key.to_vec()),
+            })
+        };
+
+        assert_eq!(setrange(b"key", 6, b"Redis"), redis::Result::Integer(11));
+        assert_eq!(
+            get(b"key"),
+            redis::Result::BulkString(b"Hello Redis".to_vec())
+        );
+        let result = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(10));
+
+        assert_eq!(setrange(b"padded", 3, b"x"), redis::Result::Integer(4));
+        assert_eq!(
+            get(b"padded"),
+            redis::Result::BulkString(b"\0\0\0x".to_vec())
+        );
+        assert_eq!(setrange(b"missing", 3, b""), redis::Result::Integer(0));
+        assert!(!redis.map.contains_key(b"missing".as_slice()));
+        assert_eq!(setrange(b"key", 100, b""), redis::Result::Integer(11));
+
+        set(&redis, b"counter", b"100", None);
+        assert_eq!(setrange(b"counter", 0, b"2"), redis::Result::Integer(3));
+        assert_eq!(get(b"counter"), redis::Result::BulkString(b"200".to_vec()));
+
+        redis.config_mut().proto_max_bulk_len = 16;
+        assert_eq!(
+            setrange(b"key", 10, b"1234567"),
+            redis::Result::Error(string_too_long())
+        );
+        assert_eq!(
+            setrange(b"other", i64::MAX, b"x"),
+            redis::Result::Error(string_too_long())
+        );
+        assert_eq!(setrange(b"key", 10, b"123456"), redis::Result::Integer(16));
+    }
+
+    #[test]
+    fn test_lcs() {
+        let redis = super::Engine::new();
+        set(&redis, b"key1", b"ohmytext", None);
+        set(&redis, b"key2", b"mynewtext", None);
+        let lcs = |len, idx, min_match_len, with_match_len| {
+            redis.call(redis::Command::Lcs {
+                key1: redis::Key(b"key1".to_vec()),
+                key2: redis::Key(b"key2".to_vec()),
+                len,
+                idx,
+                min_match_len: redis::Integer(min_match_len),
+                with_match_len,
+            })
+        };
+        let range = |start, end| {
+            redis::Result::Array(vec![
+                redis::Result::Integer(start),
+                redis::Result::Integer(end),
+            ])
+        };
+
+        assert_eq!(
+            lcs(false, false, 0, false),
+            redis::Result::BulkString(b"mytext".to_vec())
+        );
+        assert_eq!(lcs(true, false, 0, false), redis::Result::Integer(6));
+        assert_eq!(
+            lcs(false, true, 0, false),
+            redis::Result::Map(vec![
+                (
+                    redis::Result::BulkString(b"matches".to_vec()),
+                    redis::Result::Array(vec![
+                        redis::Result::Array(vec![range(4, 7), range(5, 8)]),
+                        redis::Result::Array(vec![range(2, 3), range(0, 1)]),
+                    ])
+                ),
+                (
+                    redis::Result::BulkString(b"len".to_vec()),
+                    redis::Result::Integer(6)
+                ),
+            ])
+        );
+        assert_eq!(
+            lcs(false, true, 4, true),
+            redis::Result::Map(vec![
+                (
+                    redis::Result::BulkString(b"matches".to_vec()),
+                    redis::Result::Array(vec![redis::Result::Array(vec![
+                        range(4, 7),
+                        range(5, 8),
+                        redis::Result::Integer(4)
+                    ])])
+                ),
+                (
+                    redis::Result::BulkString(b"len".to_vec()),
+                    redis::Result::Integer(6)
+                ),
+            ])
+        );
+
+        redis.call(redis::Command::Del {
+            keys: keys(&[b"key2"]),
+        });
+        assert_eq!(
+            lcs(false, false, 0, false),
+            redis::Result::BulkString(vec![])
+        );
+        redis.store(
+            redis.map.entry(b"key2".to_vec()),
+            Expirable::new_perpetual(Value::List(std::collections::VecDeque::new())),
+        );
+        assert_eq!(
+            lcs(false, false, 0, false),
+            redis::Result::Error(redis::Error::Generic(
+                "The specified keys must contain string values".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ttl() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(b"foo".to_vec()),
+            value: redis::String(b"42".to_vec()),
+            expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Ok);
+
+        let ttl = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"foo".to_vec()),
+        });
+        assert_eq!(ttl, redis::Result::Integer(1));
+
+        clock.advance(std::time::Duration::from_millis(500));
+        let ttl = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"foo".to_vec()),
+        });
+        assert_eq!(ttl, redis::Result::Integer(0));
+
+        clock.advance(std::time::Duration::from_millis(500));
+        let ttl = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"foo".to_vec()),
+        });
+        assert_eq!(ttl, redis::Result::Integer(-2));
+    }
+
+    #[test]
+    fn test_no_ttl() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(b"foo".to_vec()),
+            value: redis::String(b"42".to_vec()),
+            expiration: None,
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Ok);
+
+        let ttl = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"foo".to_vec()),
+        });
+        assert_eq!(ttl, redis::Result::Integer(-1));
+    }
+
+    #[test]
+    fn test_append() {
+        let redis = super::Engine::new();
+
+        let result = redis.call(redis::Command::Append {
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"hello".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(5));
+
+        let result = redis.call(redis::Command::Append {
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b", world!".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(13));
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"hello, world!".to_vec()));
+    }
+
     #[test]
     fn test_append_to_expired_key() {
         let redis = super::Engine::new();
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("bye!".to_string()),
-            expiration: Some(redis::Expiration::Seconds(redis::Integer(0))),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"bye!".to_vec()),
+            expiration: Some(redis::Expiration::UnixTimeSeconds(redis::Integer(1))),
             get: false,
             condition: None,
         });
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Append {
-            key: redis::Key("key".to_string()),
-            value: redis::String("hello!".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"hello!".to_vec()),
         });
         assert_eq!(result, redis::Result::Integer(6));
 
         let result = redis.call(redis::Command::Get {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
-        assert_eq!(result, redis::Result::BulkString("hello!".to_string()));
+        assert_eq!(result, redis::Result::BulkString(b"hello!".to_vec()));
     }
 
     #[test]
@@ -723,13 +2687,13 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Strlen {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Integer(0));
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("key".to_string()),
-            value: redis::String("hello, world!".to_string()),
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"hello, world!".to_vec()),
             expiration: Some(redis::Expiration::Seconds(redis::Integer(1))),
             get: false,
             condition: None,
@@ -737,14 +2701,14 @@ mod tests {
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Strlen {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Integer(13));
 
         clock.advance(std::time::Duration::from_secs(1));
 
         let result = redis.call(redis::Command::Strlen {
-            key: redis::Key("key".to_string()),
+            key: redis::Key(b"key".to_vec()),
         });
         assert_eq!(result, redis::Result::Integer(0));
     }
@@ -755,8 +2719,8 @@ mod tests {
         let redis = super::Engine::with_clock(&clock);
 
         let result = redis.call(redis::Command::Set {
-            key: redis::Key("foo".to_string()),
-            value: redis::String("42".to_string()),
+            key: redis::Key(b"foo".to_vec()),
+            value: redis::String(b"42".to_vec()),
             expiration: None,
             get: false,
             condition: None,
@@ -764,14 +2728,1105 @@ mod tests {
         assert_eq!(result, redis::Result::Ok);
 
         let result = redis.call(redis::Command::Expire {
-            key: redis::Key("foo".to_string()),
-            seconds: redis::Integer(3),
+            key: redis::Key(b"foo".to_vec()),
+            expiration: redis::Expiration::Seconds(redis::Integer(3)),
+            conditions: vec![],
         });
         assert_eq!(result, redis::Result::Integer(1));
 
         let ttl = redis.call(redis::Command::Ttl {
-            key: redis::Key("foo".to_string()),
+            key: redis::Key(b"foo".to_vec()),
         });
         assert_eq!(ttl, redis::Result::Integer(3));
     }
+
+    fn expire(
+        redis: &impl Engine,
+        key: &[u8],
+        expiration: redis::Expiration,
+        conditions: Vec<redis::ExpireCondition>,
+    ) -> redis::Result {
+        redis.call(redis::Command::Expire {
+            key: redis::Key(key.to_vec()),
+            expiration,
+            conditions,
+        })
+    }
+
+    #[test]
+    fn test_expire_in_the_past() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"a", b"1", None);
+        set(&redis, b"b", b"2", None);
+
+        let result = expire(
+            &redis,
+            b"a",
+            redis::Expiration::Seconds(redis::Integer(-1)),
+            vec![],
+        );
+        assert_eq!(result, redis::Result::Integer(1));
+        let result = expire(
+            &redis,
+            b"b",
+            redis::Expiration::UnixTimeMilliseconds(redis::Integer(1)),
+            vec![],
+        );
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::Exists {
+            keys: keys(&[b"a", b"b"]),
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+
+        let result = expire(
+            &redis,
+            b"a",
+            redis::Expiration::Seconds(redis::Integer(-1)),
+            vec![],
+        );
+        assert_eq!(result, redis::Result::Integer(0));
+
+        let result = expire(
+            &redis,
+            b"a",
+            redis::Expiration::Milliseconds(redis::Integer(i64::MAX)),
+            vec![],
+        );
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::InvalidExpireTime("pexpire"))
+        );
+    }
+
+    #[test]
+    fn test_expire_conditions() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"key", b"1", None);
+        let seconds = |s| redis::Expiration::Seconds(redis::Integer(s));
+
+        for (expiration, conditions, expected) in [
+            (seconds(10), vec![redis::ExpireCondition::IfExpiry], 0),
+            (seconds(10), vec![redis::ExpireCondition::IfGreater], 0),
+            (seconds(10), vec![redis::ExpireCondition::IfNoExpiry], 1),
+            (seconds(20), vec![redis::ExpireCondition::IfNoExpiry], 0),
+            (seconds(5), vec![redis::ExpireCondition::IfGreater], 0),
+            (seconds(20), vec![redis::ExpireCondition::IfGreater], 1),
+            (seconds(30), vec![redis::ExpireCondition::IfLess], 0),
+            (
+                seconds(15),
+                vec![
+                    redis::ExpireCondition::IfExpiry,
+                    redis::ExpireCondition::IfLess,
+                ],
+                1,
+            ),
+        ] {
+            let result = expire(&redis, b"key", expiration, conditions);
+            assert_eq!(result, redis::Result::Integer(expected));
+        }
+
+        let result = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(15));
+
+        let result = redis.call(redis::Command::Persist {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        // without a deadline, LT always applies
+        let result = expire(
+            &redis,
+            b"key",
+            seconds(100),
+            vec![redis::ExpireCondition::IfLess],
+        );
+        assert_eq!(result, redis::Result::Integer(1));
+    }
+
+    #[test]
+    fn test_pttl_expiretime_and_persist() {
+        let clock = FakeClock::new(
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1_000_000_500),
+        );
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"perpetual", b"1", None);
+        set(
+            &redis,
+            b"expiring",
+            b"2",
+            Some(redis::Expiration::Milliseconds(redis::Integer(1500))),
+        );
+
+        let call = |command: fn(redis::Key) -> redis::Command, key: &[u8]| {
+            redis.call(command(redis::Key(key.to_vec())))
+        };
+        let pttl = |key| redis::Command::PTtl { key };
+        let expiretime = |key| redis::Command::ExpireTime { key };
+        let pexpiretime = |key| redis::Command::PExpireTime { key };
+        let persist = |key| redis::Command::Persist { key };
+
+        assert_eq!(call(pttl, b"expiring"), redis::Result::Integer(1500));
+        assert_eq!(call(pttl, b"perpetual"), redis::Result::Integer(-1));
+        assert_eq!(call(pttl, b"missing"), redis::Result::Integer(-2));
+        assert_eq!(
+            call(expiretime, b"expiring"),
+            redis::Result::Integer(1_000_002)
+        );
+        assert_eq!(
+            call(pexpiretime, b"expiring"),
+            redis::Result::Integer(1_000_002_000)
+        );
+        assert_eq!(call(expiretime, b"perpetual"), redis::Result::Integer(-1));
+        assert_eq!(call(pexpiretime, b"missing"), redis::Result::Integer(-2));
+
+        assert_eq!(call(persist, b"perpetual"), redis::Result::Integer(0));
+        assert_eq!(call(persist, b"missing"), redis::Result::Integer(0));
+        assert_eq!(call(persist, b"expiring"), redis::Result::Integer(1));
+        assert_eq!(call(pttl, b"expiring"), redis::Result::Integer(-1));
+    }
+
+    fn set(redis: &impl Engine, key: &[u8], value: &[u8], expiration: Option<redis::Expiration>) {
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(key.to_vec()),
+            value: redis::String(value.to_vec()),
+            expiration,
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Ok);
+    }
+
+    fn keys(keys: &[&[u8]]) -> Vec<redis::Key> {
+        keys.iter().map(|k| redis::Key(k.to_vec())).collect()
+    }
+
+    #[test]
+    fn test_del() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"a", b"1", None);
+        set(&redis, b"b", b"2", None);
+        set(
+            &redis,
+            b"expiring",
+            b"3",
+            Some(redis::Expiration::Seconds(redis::Integer(1))),
+        );
+        clock.advance(std::time::Duration::from_secs(2));
+
+        let result = redis.call(redis::Command::Del {
+            keys: keys(&[b"a", b"missing", b"expiring", b"a"]),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::Unlink {
+            keys: keys(&[b"b"]),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Null);
+    }
+
+    #[test]
+    fn test_exists_and_touch() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"a", b"1", None);
+        set(
+            &redis,
+            b"expiring",
+            b"2",
+            Some(redis::Expiration::Seconds(redis::Integer(1))),
+        );
+        clock.advance(std::time::Duration::from_secs(2));
+
+        let result = redis.call(redis::Command::Exists {
+            keys: keys(&[b"a", b"a", b"missing", b"expiring"]),
+        });
+        assert_eq!(result, redis::Result::Integer(2));
+
+        let result = redis.call(redis::Command::Touch {
+            keys: keys(&[b"a", b"missing"]),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+    }
+
+    #[test]
+    fn test_type() {
+        let redis = super::Engine::new();
+        set(&redis, b"a", b"1", None);
+
+        let result = redis.call(redis::Command::Type {
+            key: redis::Key(b"a".to_vec()),
+        });
+        assert_eq!(result, redis::Result::SimpleString("string".to_string()));
+
+        let result = redis.call(redis::Command::Type {
+            key: redis::Key(b"missing".to_vec()),
+        });
+        assert_eq!(result, redis::Result::SimpleString("none".to_string()));
+    }
+
+    #[test]
+    fn test_rename() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(
+            &redis,
+            b"a",
+            b"1",
+            Some(redis::Expiration::Seconds(redis::Integer(10))),
+        );
+        set(&redis, b"b", b"2", None);
+
+        let result = redis.call(redis::Command::Rename {
+            key: redis::Key(b"a".to_vec()),
+            new_key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Ok);
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"1".to_vec()));
+        let result = redis.call(redis::Command::Ttl {
+            key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(10));
+        let result = redis.call(redis::Command::Exists {
+            keys: keys(&[b"a"]),
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+
+        clock.advance(std::time::Duration::from_secs(11));
+        let result = redis.call(redis::Command::Rename {
+            key: redis::Key(b"b".to_vec()),
+            new_key: redis::Key(b"c".to_vec()),
+        });
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic("no such key".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_renamenx() {
+        let redis = super::Engine::new();
+        set(&redis, b"a", b"1", None);
+        set(&redis, b"b", b"2", None);
+
+        let result = redis.call(redis::Command::RenameNx {
+            key: redis::Key(b"a".to_vec()),
+            new_key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+
+        let result = redis.call(redis::Command::RenameNx {
+            key: redis::Key(b"a".to_vec()),
+            new_key: redis::Key(b"c".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"c".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"1".to_vec()));
+
+        let result = redis.call(redis::Command::RenameNx {
+            key: redis::Key(b"a".to_vec()),
+            new_key: redis::Key(b"d".to_vec()),
+        });
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic("no such key".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rename_keeps_access_history() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"a", b"1", None);
+        clock.advance(std::time::Duration::from_secs(10));
+
+        redis.call(redis::Command::Rename {
+            key: redis::Key(b"a".to_vec()),
+            new_key: redis::Key(b"b".to_vec()),
+        });
+        let idle = redis.map.get(b"b".as_slice()).unwrap().idle(clock.now());
+        assert_eq!(idle, 10_000);
+    }
+
+    #[test]
+    fn test_rename_is_atomic() {
+        let redis = super::Engine::new();
+        set(&redis, b"a", b"v", None);
+        let done = std::sync::atomic::AtomicBool::new(false);
+
+        std::thread::scope(|s| {
+            let (redis, done) = (&redis, &done);
+            // one moves the key back and forth, the other only where it is free
+            s.spawn(move || {
+                while !done.load(Relaxed) {
+                    redis.call(redis::Command::Rename {
+                        key: redis::Key(b"a".to_vec()),
+                        new_key: redis::Key(b"b".to_vec()),
+                    });
+                    redis.call(redis::Command::Rename {
+                        key: redis::Key(b"b".to_vec()),
+                        new_key: redis::Key(b"a".to_vec()),
+                    });
+                }
+            });
+            s.spawn(move || {
+                while !done.load(Relaxed) {
+                    redis.call(redis::Command::RenameNx {
+                        key: redis::Key(b"b".to_vec()),
+                        new_key: redis::Key(b"a".to_vec()),
+                    });
+                }
+            });
+            // the writers only stop once the reader is done, even if it failed
+            let atomic = (0..1000).all(|_| {
+                let result = redis.call(redis::Command::MGet {
+                    keys: keys(&[b"a", b"b"]),
+                });
+                let redis::Result::Array(values) = result else {
+                    return false;
+                };
+                let found: Vec<_> = values
+                    .iter()
+                    .filter(|v| **v != redis::Result::Null)
+                    .collect();
+                found == [&redis::Result::BulkString(b"v".to_vec())]
+            });
+            done.store(true, Relaxed);
+            assert!(atomic);
+        });
+        assert_eq!(redis.map.len(), 1);
+    }
+
+    #[test]
+    fn test_copy() {
+        let redis = super::Engine::new();
+        set(&redis, b"a", b"1", None);
+        set(&redis, b"b", b"2", None);
+
+        let result = redis.call(redis::Command::Copy {
+            source: redis::Key(b"a".to_vec()),
+            destination: redis::Key(b"b".to_vec()),
+            replace: false,
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+
+        let result = redis.call(redis::Command::Copy {
+            source: redis::Key(b"a".to_vec()),
+            destination: redis::Key(b"b".to_vec()),
+            replace: true,
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"1".to_vec()));
+
+        let result = redis.call(redis::Command::Copy {
+            source: redis::Key(b"missing".to_vec()),
+            destination: redis::Key(b"c".to_vec()),
+            replace: false,
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+
+        let result = redis.call(redis::Command::Copy {
+            source: redis::Key(b"a".to_vec()),
+            destination: redis::Key(b"a".to_vec()),
+            replace: true,
+        });
+        assert_eq!(
+            result,
+            redis::Result::Error(redis::Error::Generic(
+                "source and destination objects are the same".to_string()
+            ))
+        );
+    }
+
+    fn scan(
+        redis: &impl Engine,
+        cursor: u64,
+        pattern: Option<&str>,
+        count: i64,
+    ) -> (u64, Vec<Vec<u8>>) {
+        let result = redis.call(redis::Command::Scan {
+            cursor,
+            pattern: pattern.map(|p| redis::String(p.as_bytes().to_vec())),
+            count: redis::Integer(count),
+            kind: None,
+        });
+        let redis::Result::Array(reply) = result else {
+            panic!("unexpected reply {:?}", result);
+        };
+        match &reply[..] {
+            [redis::Result::BulkString(next), redis::Result::Array(keys)] => (
+                std::str::from_utf8(next).unwrap().parse().unwrap(),
+                keys.iter()
+                    .map(|k| match k {
+                        redis::Result::BulkString(k) => k.clone(),
+                        k => panic!("unexpected key {:?}", k),
+                    })
+                    .collect(),
+            ),
+            _ => panic!("unexpected reply {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(&redis, b"hello", b"1", None);
+        set(&redis, b"hallo", b"2", None);
+        set(&redis, b"world", b"3", None);
+        set(
+            &redis,
+            b"hxllo",
+            b"4",
+            Some(redis::Expiration::Seconds(redis::Integer(1))),
+        );
+        clock.advance(std::time::Duration::from_secs(2));
+
+        let result = redis.call(redis::Command::Keys {
+            pattern: redis::String(b"h?llo".to_vec()),
+        });
+        let redis::Result::Array(keys) = result else {
+            panic!("unexpected reply {:?}", result);
+        };
+        let mut keys: Vec<_> = keys
+            .into_iter()
+            .map(|k| match k {
+                redis::Result::BulkString(k) => k,
+                k => panic!("unexpected key {:?}", k),
+            })
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec![b"hallo".to_vec(), b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn test_scan() {
+        let redis = super::Engine::new();
+        for i in 0..100 {
+            set(&redis, format!("key:{}", i).as_bytes(), b"v", None);
+        }
+
+        // keys present for the whole iteration are returned exactly once,
+        // however the keyspace changes in between calls
+        let mut seen = Vec::new();
+        let mut cursor = 0;
+        let mut added = 0;
+        loop {
+            // a batch only looks at the keys it returns
+            let (next, keys) = scan(&redis, cursor, None, 7);
+            assert!(keys.len() == 7 || next == 0);
+            seen.extend(keys);
+            redis.call(redis::Command::Del {
+                keys: keys_with_prefix("key:", added..added + 1),
+            });
+            for i in 0..2 {
+                set(
+                    &redis,
+                    format!("new:{}:{}", added, i).as_bytes(),
+                    b"v",
+                    None,
+                );
+            }
+            added += 1;
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        for i in added..100 {
+            let key = format!("key:{}", i).into_bytes();
+            assert_eq!(seen.iter().filter(|k| **k == key).count(), 1);
+        }
+        let indexed: usize = redis.hashes.iter().map(|h| h.lock().unwrap().len()).sum();
+        assert_eq!(indexed, redis.map.len());
+
+        let (next, keys) = scan(&redis, 0, Some("key:1?"), 1000);
+        assert_eq!(next, 0);
+        assert_eq!(keys.len(), (10..20).filter(|i| *i >= added).count());
+
+        let result = redis.call(redis::Command::Scan {
+            cursor: 0,
+            pattern: None,
+            count: redis::Integer(1000),
+            kind: Some(redis::String(b"list".to_vec())),
+        });
+        assert_eq!(
+            result,
+            redis::Result::Array(vec![
+                redis::Result::BulkString(b"0".to_vec()),
+                redis::Result::Array(vec![]),
+            ])
+        );
+    }
+
+    fn keys_with_prefix(prefix: &str, range: std::ops::Range<usize>) -> Vec<redis::Key> {
+        range
+            .map(|i| redis::Key(format!("{}{}", prefix, i).into_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        for i in 0..1000 {
+            set(
+                &redis,
+                format!("expiring:{}", i).as_bytes(),
+                b"v",
+                Some(redis::Expiration::Seconds(redis::Integer(1))),
+            );
+        }
+        for i in 0..100 {
+            set(&redis, format!("perpetual:{}", i).as_bytes(), b"v", None);
+        }
+
+        assert_eq!(redis.active_expire_cycle(std::time::Duration::ZERO), 0);
+        assert_eq!(redis.map.len(), 1100);
+
+        clock.advance(std::time::Duration::from_secs(2));
+        let deleted = redis.active_expire_cycle(std::time::Duration::from_millis(25));
+        assert_eq!(deleted, 1000);
+        assert_eq!(redis.map.len(), 100);
+        assert!(redis.expires().is_empty());
+        assert_eq!(
+            redis.stats(),
+            Stats {
+                expired_keys: 1000,
+                expired_time_cap_reached_count: 0,
+                evicted_keys: 0,
+            }
+        );
+
+        let result = redis.call(redis::Command::ConfigResetStat);
+        assert_eq!(result, redis::Result::Ok);
+        assert_eq!(redis.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_active_expire_cycle_budget() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        for i in 0..100 {
+            set(
+                &redis,
+                format!("key:{}", i).as_bytes(),
+                b"v",
+                Some(redis::Expiration::Seconds(redis::Integer(1))),
+            );
+        }
+        clock.advance(std::time::Duration::from_secs(2));
+
+        // the clock does not move, so a zero budget allows a single loop
+        let deleted = redis.active_expire_cycle(std::time::Duration::ZERO);
+        assert_eq!(deleted, ACTIVE_EXPIRE_KEYS_PER_LOOP);
+        assert_eq!(redis.stats().expired_time_cap_reached_count, 1);
+        assert_eq!(redis.map.len(), 100 - ACTIVE_EXPIRE_KEYS_PER_LOOP);
+    }
+
+    fn indexed<C: Clock>(redis: &super::Engine<'_, C>) -> Vec<(u64, Vec<u8>)> {
+        redis
+            .expires()
+            .iter()
+            .map(|(t, k)| {
+                let secs = t.duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap();
+                (secs.as_secs(), k.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_expiration_index() {
+        let clock = FakeClock::new(std::time::SystemTime::UNIX_EPOCH);
+        let redis = super::Engine::with_clock(&clock);
+        let ex = |s| Some(redis::Expiration::Seconds(redis::Integer(s)));
+        set(&redis, b"a", b"v", ex(10));
+        set(&redis, b"b", b"v", ex(20));
+        set(&redis, b"c", b"v", ex(30));
+        set(&redis, b"d", b"v", None);
+        assert_eq!(
+            indexed(&redis),
+            vec![
+                (10, b"a".to_vec()),
+                (20, b"b".to_vec()),
+                (30, b"c".to_vec())
+            ]
+        );
+        assert_eq!(
+            redis.next_expiration(),
+            Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(10))
+        );
+
+        redis.call(redis::Command::Persist {
+            key: redis::Key(b"a".to_vec()),
+        });
+        redis.call(redis::Command::Expire {
+            key: redis::Key(b"d".to_vec()),
+            expiration: redis::Expiration::Seconds(redis::Integer(5)),
+            conditions: vec![],
+        });
+        redis.call(redis::Command::Rename {
+            key: redis::Key(b"b".to_vec()),
+            new_key: redis::Key(b"c".to_vec()),
+        });
+        assert_eq!(
+            indexed(&redis),
+            vec![(5, b"d".to_vec()), (20, b"c".to_vec())]
+        );
+
+        redis.call(redis::Command::Copy {
+            source: redis::Key(b"c".to_vec()),
+            destination: redis::Key(b"a".to_vec()),
+            replace: true,
+        });
+        set(&redis, b"d", b"v", None);
+        assert_eq!(
+            indexed(&redis),
+            vec![(20, b"a".to_vec()), (20, b"c".to_vec())]
+        );
+
+        redis.call(redis::Command::Del {
+            keys: keys(&[b"a", b"c"]),
+        });
+        assert!(indexed(&redis).is_empty());
+        assert_eq!(redis.next_expiration(), None);
+    }
+
+    #[test]
+    fn test_active_expire_cycle_keeps_live_keys() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(
+            &redis,
+            b"short",
+            b"v",
+            Some(redis::Expiration::Seconds(redis::Integer(1))),
+        );
+        set(
+            &redis,
+            b"long",
+            b"v",
+            Some(redis::Expiration::Seconds(redis::Integer(10))),
+        );
+        clock.advance(std::time::Duration::from_secs(1));
+
+        assert_eq!(redis.active_expire_cycle(std::time::Duration::ZERO), 1);
+        assert_eq!(redis.map.len(), 1);
+        assert_eq!(redis.expires().len(), 1);
+    }
+
+    #[test]
+    fn test_lazily_expired_keys_are_counted() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set(
+            &redis,
+            b"key",
+            b"v",
+            Some(redis::Expiration::Seconds(redis::Integer(1))),
+        );
+        clock.advance(std::time::Duration::from_secs(1));
+
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"key".to_vec()),
+        });
+        assert_eq!(result, redis::Result::Null);
+        assert_eq!(redis.stats().expired_keys, 1);
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let redis = super::Engine::new();
+        let list = Value::List(std::collections::VecDeque::from([b"v".to_vec()]));
+        redis.store(
+            redis.map.entry(b"list".to_vec()),
+            Expirable::new_perpetual(list.clone()),
+        );
+        let key = || redis::Key(b"list".to_vec());
+        let wrong_type = redis::Result::Error(redis::Error::WrongType);
+
+        assert_eq!(redis.call(redis::Command::Get { key: key() }), wrong_type);
+        assert_eq!(
+            redis.call(redis::Command::Incr {
+                key: key(),
+                increment: redis::Integer(1),
+            }),
+            wrong_type
+        );
+        assert_eq!(
+            redis.call(redis::Command::Strlen { key: key() }),
+            wrong_type
+        );
+        let result = redis.call(redis::Command::Append {
+            key: key(),
+            value: redis::String(b"w".to_vec()),
+        });
+        assert_eq!(result, wrong_type);
+        let result = redis.call(redis::Command::Set {
+            key: key(),
+            value: redis::String(b"w".to_vec()),
+            expiration: None,
+            get: true,
+            condition: None,
+        });
+        assert_eq!(result, wrong_type);
+        assert_eq!(redis.map.get(b"list".as_slice()).unwrap().value, list);
+
+        let result = redis.call(redis::Command::Type { key: key() });
+        assert_eq!(result, redis::Result::SimpleString("list".to_string()));
+        let result = redis.call(redis::Command::Scan {
+            cursor: 0,
+            pattern: None,
+            count: redis::Integer(10),
+            kind: Some(redis::String(b"LIST".to_vec())),
+        });
+        assert_eq!(
+            result,
+            redis::Result::Array(vec![
+                redis::Result::BulkString(b"0".to_vec()),
+                redis::Result::Array(vec![redis::Result::BulkString(b"list".to_vec())]),
+            ])
+        );
+
+        // a plain SET replaces a value of any type
+        set(&redis, b"list", b"w", None);
+        let result = redis.call(redis::Command::Type { key: key() });
+        assert_eq!(result, redis::Result::SimpleString("string".to_string()));
+    }
+
+    #[test]
+    fn test_used_memory() {
+        let redis = super::Engine::new();
+        set(&redis, b"key", b"value", None);
+        assert_eq!(redis.used_memory(), 2 * 3 + 5 + ENTRY_OVERHEAD);
+
+        redis.call(redis::Command::Append {
+            key: redis::Key(b"key".to_vec()),
+            value: redis::String(b"s".to_vec()),
+        });
+        set(&redis, b"counter", b"9", None);
+        redis.call(redis::Command::Incr {
+            key: redis::Key(b"counter".to_vec()),
+            increment: redis::Integer(1),
+        });
+        // the counter is kept as an integer
+        assert_eq!(
+            redis.used_memory(),
+            2 * (3 + 7) + 6 + 8 + 2 * ENTRY_OVERHEAD
+        );
+
+        redis.call(redis::Command::Rename {
+            key: redis::Key(b"key".to_vec()),
+            new_key: redis::Key(b"k".to_vec()),
+        });
+        assert_eq!(
+            redis.used_memory(),
+            2 * (1 + 7) + 6 + 8 + 2 * ENTRY_OVERHEAD
+        );
+
+        redis.call(redis::Command::Del {
+            keys: keys(&[b"k", b"counter"]),
+        });
+        assert_eq!(redis.used_memory(), 0);
+    }
+
+    fn limit<C: Clock>(redis: &super::Engine<'_, C>, keys: usize, policy: config::EvictionPolicy) {
+        {
+            let mut config = redis.config_mut();
+            // room for `keys` keys like the ones `set_keys` sets, and for their
+            // copies in the keys sampled
+            config.maxmemory = keys * key_size(policy);
+            config.maxmemory_policy = policy;
+        }
+        redis.resample();
+    }
+
+    fn key_size(policy: config::EvictionPolicy) -> usize {
+        use config::EvictionPolicy as P;
+        let sampled = match policy {
+            P::AllKeysLru | P::AllKeysLfu | P::AllKeysRandom => 2 * 2 + INDEX_OVERHEAD,
+            _ => 0,
+        };
+        2 * 2 + 1 + ENTRY_OVERHEAD + sampled
+    }
+
+    fn set_keys<C: Clock>(redis: &super::Engine<'_, C>, prefix: u8, n: u8) {
+        for i in 0..n {
+            set(redis, &[prefix, b'0' + i], b"v", None);
+        }
+    }
+
+    #[test]
+    fn test_noeviction() {
+        let redis = super::Engine::new();
+        limit(&redis, 2, config::EvictionPolicy::NoEviction);
+        set_keys(&redis, b'k', 3);
+        assert_eq!(redis.map.len(), 3);
+
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(b"k3".to_vec()),
+            value: redis::String(b"v".to_vec()),
+            expiration: None,
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Error(redis::Error::Oom));
+        // commands that do not add data still work
+        let result = redis.call(redis::Command::Del {
+            keys: keys(&[b"k0"]),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+        assert_eq!(redis.stats().evicted_keys, 0);
+    }
+
+    #[test]
+    fn test_allkeys_lru() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set_keys(&redis, b'o', 5);
+        clock.advance(std::time::Duration::from_secs(10));
+        set_keys(&redis, b'n', 5);
+        // sampling every key makes the eviction exact
+        limit(&redis, 5, config::EvictionPolicy::AllKeysLru);
+        redis.config_mut().maxmemory_samples = 64;
+
+        set(&redis, b"x", b"v", None);
+        assert_eq!(redis.stats().evicted_keys, 5);
+        assert!(redis.map.iter().all(|e| e.key()[0] != b'o'));
+        let limit = redis.config().maxmemory + key_size(config::EvictionPolicy::AllKeysLru);
+        assert!(redis.used_memory() <= limit);
+    }
+
+    #[test]
+    fn test_allkeys_lfu() {
+        let clock = FakeClock::new_now();
+        let redis = super::Engine::with_clock(&clock);
+        set_keys(&redis, b'k', 4);
+        for _ in 0..100 {
+            redis.call(redis::Command::Get {
+                key: redis::Key(b"k0".to_vec()),
+            });
+        }
+        limit(&redis, 1, config::EvictionPolicy::AllKeysLfu);
+        redis.config_mut().maxmemory_samples = 64;
+
+        set(&redis, b"x", b"v", None);
+        assert_eq!(redis.stats().evicted_keys, 3);
+        assert!(redis.map.contains_key(b"k0".as_slice()));
+    }
+
+    #[test]
+    fn test_volatile_ttl() {
+        let redis = super::Engine::new();
+        let ex = |s| Some(redis::Expiration::Seconds(redis::Integer(s)));
+        set(&redis, b"k0", b"v", ex(30));
+        set(&redis, b"k1", b"v", ex(10));
+        set(&redis, b"k2", b"v", None);
+        limit(&redis, 2, config::EvictionPolicy::VolatileTtl);
+        // and for one deadline
+        redis.config_mut().maxmemory += 2 + INDEX_OVERHEAD;
+
+        set(&redis, b"k3", b"v", None);
+        assert_eq!(redis.stats().evicted_keys, 1);
+        assert!(!redis.map.contains_key(b"k1".as_slice()));
+
+        // once no key has a deadline left, there is nothing to evict
+        set(&redis, b"k4", b"v", None);
+        assert!(!redis.map.contains_key(b"k0".as_slice()));
+        let result = redis.call(redis::Command::Set {
+            key: redis::Key(b"k5".to_vec()),
+            value: redis::String(b"v".to_vec()),
+            expiration: None,
+            get: false,
+            condition: None,
+        });
+        assert_eq!(result, redis::Result::Error(redis::Error::Oom));
+    }
+
+    #[test]
+    fn test_sampling_follows_policy() {
+        let redis = super::Engine::new();
+        let ex = Some(redis::Expiration::Seconds(redis::Integer(10)));
+        set(&redis, b"k0", b"v", ex);
+        set(&redis, b"k1", b"v", None);
+        // without eviction, nothing is sampled
+        assert!(redis.sampled().keys.is_empty());
+        let entries = 2 * (2 * 2 + 1 + ENTRY_OVERHEAD) + 2 + INDEX_OVERHEAD;
+        assert_eq!(redis.used_memory(), entries);
+
+        let config_set = |parameters: &[(&[u8], &[u8])]| {
+            let parameters = parameters
+                .iter()
+                .map(|(n, v)| (redis::String(n.to_vec()), redis::String(v.to_vec())))
+                .collect();
+            let result = redis.call(redis::Command::ConfigSet { parameters });
+            assert_eq!(result, redis::Result::Ok);
+        };
+        config_set(&[
+            (b"maxmemory", b"1mb"),
+            (b"maxmemory-policy", b"allkeys-lru"),
+        ]);
+        assert_eq!(redis.sampled().keys.len(), 2);
+        set(&redis, b"k2", b"v", None);
+        assert_eq!(redis.sampled().keys.len(), 3);
+
+        config_set(&[(b"maxmemory-policy", b"volatile-lru")]);
+        assert_eq!(redis.sampled().keys.sample(1), vec![b"k0".to_vec()]);
+        assert_eq!(
+            redis.used_memory(),
+            entries + 2 * 2 + 1 + ENTRY_OVERHEAD + 2 * 2 + INDEX_OVERHEAD
+        );
+
+        config_set(&[(b"maxmemory", b"0")]);
+        assert!(redis.sampled().keys.is_empty());
+        assert_eq!(redis.used_memory(), entries + 2 * 2 + 1 + ENTRY_OVERHEAD);
+    }
+
+    #[test]
+    fn test_victim_samples_again() {
+        let redis = super::Engine::new();
+        limit(&redis, 1, config::EvictionPolicy::AllKeysRandom);
+        set(&redis, b"k0", b"v", None);
+        // a sampled key can be deleted before it is looked up
+        redis.sampled().keys.insert(b"gone");
+        for _ in 0..10 {
+            let victim = redis.victim(config::EvictionPolicy::AllKeysRandom, 5);
+            assert_eq!(victim, Some(b"k0".to_vec()));
+        }
+
+        // but it gives up if only such keys are left
+        redis.call(redis::Command::Del {
+            keys: keys(&[b"k0"]),
+        });
+        redis.sampled().keys.insert(b"gone");
+        let victim = redis.victim(config::EvictionPolicy::AllKeysRandom, 5);
+        assert_eq!(victim, None);
+    }
+
+    #[test]
+    fn test_lfu_counter() {
+        let clock = FakeClock::new_now();
+        let e = Expirable::new_perpetual(());
+        e.created(clock.now());
+        assert_eq!(e.frequency(clock.now()), LFU_INIT_VAL);
+        for _ in 0..1000 {
+            e.touch(clock.now());
+        }
+        let frequency = e.frequency(clock.now());
+        assert!(frequency > LFU_INIT_VAL && frequency < 255);
+
+        clock.advance(std::time::Duration::from_secs(60 * 3));
+        assert_eq!(e.frequency(clock.now()), frequency - 3);
+        assert_eq!(e.idle(clock.now()), 180_000);
+    }
+
+    fn pairs(keys: &[Vec<u8>], value: &[u8]) -> Vec<(redis::Key, redis::String)> {
+        keys.iter()
+            .map(|k| (redis::Key(k.clone()), redis::String(value.to_vec())))
+            .collect()
+    }
+
+    #[test]
+    fn test_mget_and_mset() {
+        let redis = super::Engine::new();
+        let result = redis.call(redis::Command::MSet {
+            pairs: vec![
+                (redis::Key(b"a".to_vec()), redis::String(b"1".to_vec())),
+                (redis::Key(b"b".to_vec()), redis::String(b"2".to_vec())),
+                (redis::Key(b"a".to_vec()), redis::String(b"3".to_vec())),
+            ],
+        });
+        assert_eq!(result, redis::Result::Ok);
+        redis.store(
+            redis.map.entry(b"list".to_vec()),
+            Expirable::new_perpetual(Value::List(std::collections::VecDeque::new())),
+        );
+
+        let result = redis.call(redis::Command::MGet {
+            keys: keys(&[b"a", b"b", b"c", b"list"]),
+        });
+        assert_eq!(
+            result,
+            redis::Result::Array(vec![
+                redis::Result::BulkString(b"3".to_vec()),
+                redis::Result::BulkString(b"2".to_vec()),
+                redis::Result::Null,
+                redis::Result::Null,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_msetnx() {
+        let redis = super::Engine::new();
+        let result = redis.call(redis::Command::MSetNx {
+            pairs: pairs(&[b"a".to_vec(), b"b".to_vec()], b"1"),
+        });
+        assert_eq!(result, redis::Result::Integer(1));
+
+        let result = redis.call(redis::Command::MSetNx {
+            pairs: pairs(&[b"c".to_vec(), b"b".to_vec()], b"2"),
+        });
+        assert_eq!(result, redis::Result::Integer(0));
+        assert!(!redis.map.contains_key(b"c".as_slice()));
+        let result = redis.call(redis::Command::Get {
+            key: redis::Key(b"b".to_vec()),
+        });
+        assert_eq!(result, redis::Result::BulkString(b"1".to_vec()));
+    }
+
+    #[test]
+    fn test_mset_is_atomic() {
+        let redis = super::Engine::new();
+        let keys: Vec<Vec<u8>> = (0..64u8).map(|i| vec![i]).collect();
+        redis.call(redis::Command::MSet {
+            pairs: pairs(&keys, b"0"),
+        });
+
+        std::thread::scope(|s| {
+            for i in 1..4u8 {
+                let (redis, keys) = (&redis, &keys);
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        // in a different order for each writer
+                        let mut keys = keys.clone();
+                        keys.rotate_left(i as usize * 10);
+                        redis.call(redis::Command::MSet {
+                            pairs: pairs(&keys, &[b'0' + i]),
+                        });
+                    }
+                });
+            }
+            for _ in 0..200 {
+                let result = redis.call(redis::Command::MGet {
+                    keys: keys.iter().map(|k| redis::Key(k.clone())).collect(),
+                });
+                let redis::Result::Array(values) = result else {
+                    panic!("{:?}", result);
+                };
+                assert!(values.iter().all(|v| *v == values[0]), "{:?}", values);
+            }
+        });
+    }
 }
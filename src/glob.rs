@@ -0,0 +1,163 @@
+// Redis-style glob matching, ported from `stringmatchlen`: `*` and `?`
+// wildcards, `[...]` classes with ranges and `^` negation, and `\` escapes.
+pub fn matches(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    matches_impl(pattern, string, nocase, &mut false, 0)
+}
+
+// Once a `*` has failed to match against every suffix of the string, any
+// outer `*` would fail as well: `skip_longer` short-circuits them so that
+// patterns like `a*a*a*a*b` stay linear instead of exponential.
+fn matches_impl(
+    mut pattern: &[u8],
+    mut string: &[u8],
+    nocase: bool,
+    skip_longer: &mut bool,
+    nesting: usize,
+) -> bool {
+    if nesting > 1000 {
+        return false;
+    }
+    while !pattern.is_empty() && !string.is_empty() {
+        match pattern[0] {
+            b'*' => {
+                while pattern.get(1) == Some(&b'*') {
+                    pattern = &pattern[1..];
+                }
+                if pattern.len() == 1 {
+                    return true;
+                }
+                while !string.is_empty() {
+                    if matches_impl(&pattern[1..], string, nocase, skip_longer, nesting + 1) {
+                        return true;
+                    }
+                    if *skip_longer {
+                        return false;
+                    }
+                    string = &string[1..];
+                }
+                *skip_longer = true;
+                return false;
+            }
+            b'?' => string = &string[1..],
+            b'[' => {
+                pattern = &pattern[1..];
+                let not = pattern.first() == Some(&b'^');
+                if not {
+                    pattern = &pattern[1..];
+                }
+                let mut matched = false;
+                // leaves `pattern` on the closing `]`, or empty if there is none
+                loop {
+                    match pattern {
+                        [] => break,
+                        [b'\\', escaped, ..] => {
+                            pattern = &pattern[1..];
+                            matched |= equal(*escaped, string[0], nocase);
+                        }
+                        [b']', ..] => break,
+                        [start, b'-', end, ..] => {
+                            let (mut start, mut end, mut c) = (*start, *end, string[0]);
+                            if start > end {
+                                std::mem::swap(&mut start, &mut end);
+                            }
+                            if nocase {
+                                start = start.to_ascii_lowercase();
+                                end = end.to_ascii_lowercase();
+                                c = c.to_ascii_lowercase();
+                            }
+                            pattern = &pattern[2..];
+                            matched |= start <= c && c <= end;
+                        }
+                        [c, ..] => matched |= equal(*c, string[0], nocase),
+                    }
+                    pattern = &pattern[1..];
+                }
+                if matched == not {
+                    return false;
+                }
+                string = &string[1..];
+            }
+            b'\\' if pattern.len() >= 2 => {
+                pattern = &pattern[1..];
+                if !equal(pattern[0], string[0], nocase) {
+                    return false;
+                }
+                string = &string[1..];
+            }
+            c => {
+                if !equal(c, string[0], nocase) {
+                    return false;
+                }
+                string = &string[1..];
+            }
+        }
+        pattern = pattern.get(1..).unwrap_or_default();
+    }
+    if string.is_empty() {
+        while pattern.first() == Some(&b'*') {
+            pattern = &pattern[1..];
+        }
+    }
+    pattern.is_empty() && string.is_empty()
+}
+
+fn equal(a: u8, b: u8, nocase: bool) -> bool {
+    if nocase {
+        a.eq_ignore_ascii_case(&b)
+    } else {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches(b"*", b"", false));
+        assert!(matches(b"*", b"anything", false));
+        assert!(matches(b"h?llo", b"hello", false));
+        assert!(!matches(b"h?llo", b"hllo", false));
+        assert!(matches(b"h*llo", b"hllo", false));
+        assert!(matches(b"h*llo", b"heeeello", false));
+        assert!(matches(b"*max*", b"maxclients", false));
+        assert!(!matches(b"max*", b"proto-max-bulk-len", false));
+        assert!(!matches(b"", b"a", false));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(matches(b"h[ae]llo", b"hello", false));
+        assert!(matches(b"h[ae]llo", b"hallo", false));
+        assert!(!matches(b"h[ae]llo", b"hillo", false));
+        assert!(matches(b"h[^e]llo", b"hallo", false));
+        assert!(!matches(b"h[^e]llo", b"hello", false));
+        assert!(matches(b"h[a-b]llo", b"hbllo", false));
+        assert!(matches(b"h[b-a]llo", b"hbllo", false));
+        assert!(!matches(b"h[a-b]llo", b"hcllo", false));
+        assert!(matches(b"[\\]]", b"]", false));
+        // an unterminated class runs to the end of the pattern
+        assert!(matches(b"a[bc", b"ab", false));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(matches(b"h\\*llo", b"h*llo", false));
+        assert!(!matches(b"h\\*llo", b"hello", false));
+        assert!(matches(b"trailing\\", b"trailing\\", false));
+    }
+
+    #[test]
+    fn test_nocase() {
+        assert!(matches(b"MAX*", b"maxclients", true));
+        assert!(!matches(b"MAX*", b"maxclients", false));
+        assert!(matches(b"[A-C]", b"b", true));
+    }
+
+    #[test]
+    fn test_pathological_pattern() {
+        let pattern = b"a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*a*b";
+        assert!(!matches(pattern, &[b'a'; 100], false));
+    }
+}
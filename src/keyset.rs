@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+// A set of keys that can be sampled at random in constant time, which the
+// engine's map cannot do: keys are kept in a vector, and removed by swapping
+// the last one in their place.
+#[derive(Debug, Default)]
+pub struct KeySet {
+    keys: Vec<Vec<u8>>,
+    positions: HashMap<Vec<u8>, usize>,
+}
+
+impl KeySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn insert(&mut self, key: &[u8]) -> bool {
+        if self.positions.contains_key(key) {
+            return false;
+        }
+        self.positions.insert(key.to_vec(), self.keys.len());
+        self.keys.push(key.to_vec());
+        true
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        let Some(i) = self.positions.remove(key) else {
+            return false;
+        };
+        self.keys.swap_remove(i);
+        if let Some(moved) = self.keys.get(i) {
+            self.positions.insert(moved.clone(), i);
+        }
+        true
+    }
+
+    // `n` keys picked at random, possibly more than once.
+    pub fn sample(&self, n: usize) -> Vec<Vec<u8>> {
+        if self.keys.is_empty() {
+            return Vec::new();
+        }
+        (0..n)
+            .map(|_| self.keys[fastrand::usize(..self.keys.len())].clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut set = KeySet::new();
+        assert!(set.insert(b"a"));
+        assert!(set.insert(b"b"));
+        assert!(set.insert(b"c"));
+        assert!(!set.insert(b"a"));
+        assert_eq!(set.len(), 3);
+
+        assert!(set.remove(b"a"));
+        assert!(!set.remove(b"a"));
+        assert!(set.remove(b"c"));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.sample(3), vec![b"b".to_vec(); 3]);
+
+        assert!(set.remove(b"b"));
+        assert!(set.is_empty());
+        assert!(set.sample(3).is_empty());
+    }
+
+    #[test]
+    fn test_sample_covers_every_key() {
+        let mut set = KeySet::new();
+        for i in 0..10u8 {
+            set.insert(&[i]);
+        }
+        set.remove(&[3]);
+
+        let mut sampled = set.sample(1000);
+        sampled.sort();
+        sampled.dedup();
+        let expected: Vec<_> = (0..10u8).filter(|i| *i != 3).map(|i| vec![i]).collect();
+        assert_eq!(sampled, expected);
+    }
+}
@@ -0,0 +1,122 @@
+// A run of bytes common to both strings, as LCS IDX reports it: the
+// inclusive ranges it spans in each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+}
+
+impl Match {
+    pub fn match_len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+// The longest common subsequence of `a` and `b`, and the runs it is made of
+// from last to first, ported from Redis' `lcsCommand` so that ties are broken
+// the same way. Takes O(len(a) * len(b)) time and memory.
+pub fn lcs(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<Match>) {
+    // `table[i * (b.len() + 1) + j]` is the length of the LCS of `a[..i]` and
+    // `b[..j]`
+    let width = b.len() + 1;
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (a.len(), b.len());
+    let mut result = vec![0; table[i * width + j] as usize];
+    let mut k = result.len();
+    let mut matches = Vec::new();
+    let mut current: Option<Match> = None;
+    while i > 0 && j > 0 {
+        let mut emit = false;
+        if a[i - 1] == b[j - 1] {
+            result[k - 1] = a[i - 1];
+            match &mut current {
+                None => {
+                    current = Some(Match {
+                        a: (i - 1, i - 1),
+                        b: (j - 1, j - 1),
+                    })
+                }
+                // extend the run backwards while it is contiguous
+                Some(m) if m.a.0 == i && m.b.0 == j => {
+                    m.a.0 -= 1;
+                    m.b.0 -= 1;
+                }
+                Some(_) => emit = true,
+            }
+            // the run cannot go on past the start of either string
+            if current.as_ref().is_some_and(|m| m.a.0 == 0 || m.b.0 == 0) {
+                emit = true;
+            }
+            k -= 1;
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            emit = current.is_some();
+        }
+        if emit {
+            matches.extend(current.take());
+        }
+    }
+    (result, matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lcs() {
+        let (result, matches) = lcs(b"ohmytext", b"mynewtext");
+        assert_eq!(result, b"mytext");
+        assert_eq!(
+            matches,
+            vec![
+                Match {
+                    a: (4, 7),
+                    b: (5, 8)
+                },
+                Match {
+                    a: (2, 3),
+                    b: (0, 1)
+                },
+            ]
+        );
+        assert_eq!(matches[0].match_len(), 4);
+        assert_eq!(matches[1].match_len(), 2);
+    }
+
+    #[test]
+    fn test_no_common_subsequence() {
+        assert_eq!(lcs(b"abc", b"xyz"), (vec![], vec![]));
+        assert_eq!(lcs(b"", b"xyz"), (vec![], vec![]));
+        assert_eq!(lcs(b"abc", b""), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_identical_strings() {
+        let (result, matches) = lcs(b"same", b"same");
+        assert_eq!(result, b"same");
+        assert_eq!(
+            matches,
+            vec![Match {
+                a: (0, 3),
+                b: (0, 3)
+            }]
+        );
+    }
+}
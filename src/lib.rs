@@ -1,5 +1,12 @@
+pub mod clients;
+pub mod commands;
+pub mod config;
 pub mod dashmap;
+pub mod glob;
+pub mod keyset;
+pub mod lcs;
 pub mod redis;
 pub mod resp;
 pub mod resp_cmd;
 pub mod server;
+pub mod value;
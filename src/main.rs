@@ -1,3 +1,4 @@
 fn main() -> std::io::Result<()> {
-    rosso::server::start("127.0.0.1:6379")
+    let config = rosso::config::Config::from_args(std::env::args().skip(1))?;
+    rosso::server::start(config)
 }
@@ -2,16 +2,130 @@
 pub enum Result {
     Null,
     Ok,
-    BulkString(std::string::String),
+    SimpleString(std::string::String),
+    BulkString(Vec<u8>),
     Integer(i64),
-    Error(std::string::String),
+    Error(Error),
+    Array(Vec<Result>),
+    Map(Vec<(Result, Result)>),
+    Set(Vec<Result>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(std::string::String),
+    VerbatimString(std::string::String),
 }
 
 #[derive(Debug, PartialEq)]
-pub struct Key(pub std::string::String);
+pub enum Error {
+    Generic(std::string::String),
+    Syntax,
+    NotAnInteger,
+    NotAFloat,
+    Overflow,
+    InvalidExpireTime(&'static str),
+    WrongNumberOfArguments(std::string::String),
+    UnknownCommand {
+        name: std::string::String,
+        args: Vec<std::string::String>,
+    },
+    UnknownSubcommand {
+        command: std::string::String,
+        subcommand: std::string::String,
+    },
+    Protocol(std::string::String),
+    WrongType,
+    NoScript,
+    Busy,
+    NoAuth,
+    WrongPass,
+    ReadOnly,
+    Oom,
+    ExecAbort,
+    NoProto,
+    Loading,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Generic(_)
+            | Error::Syntax
+            | Error::NotAnInteger
+            | Error::NotAFloat
+            | Error::Overflow
+            | Error::InvalidExpireTime(_)
+            | Error::WrongNumberOfArguments(_)
+            | Error::UnknownCommand { .. }
+            | Error::UnknownSubcommand { .. }
+            | Error::Protocol(_) => "ERR",
+            Error::WrongType => "WRONGTYPE",
+            Error::NoScript => "NOSCRIPT",
+            Error::Busy => "BUSY",
+            Error::NoAuth => "NOAUTH",
+            Error::WrongPass => "WRONGPASS",
+            Error::ReadOnly => "READONLY",
+            Error::Oom => "OOM",
+            Error::ExecAbort => "EXECABORT",
+            Error::NoProto => "NOPROTO",
+            Error::Loading => "LOADING",
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ", self.code())?;
+        match self {
+            Error::Generic(message) => write!(f, "{}", message),
+            Error::Syntax => write!(f, "syntax error"),
+            Error::NotAnInteger => write!(f, "value is not an integer or out of range"),
+            Error::NotAFloat => write!(f, "value is not a valid float"),
+            Error::Overflow => write!(f, "increment or decrement would overflow"),
+            Error::InvalidExpireTime(command) => {
+                write!(f, "invalid expire time in '{}' command", command)
+            }
+            Error::WrongNumberOfArguments(command) => {
+                write!(f, "wrong number of arguments for '{}' command", command)
+            }
+            Error::UnknownCommand { name, args } => {
+                write!(f, "unknown command '{}', with args beginning with: ", name)?;
+                args.iter().try_for_each(|arg| write!(f, "'{}' ", arg))
+            }
+            Error::UnknownSubcommand {
+                command,
+                subcommand,
+            } => write!(
+                f,
+                "unknown subcommand '{}'. Try {} HELP.",
+                subcommand, command
+            ),
+            Error::Protocol(message) => write!(f, "Protocol error: {}", message),
+            Error::WrongType => {
+                write!(f, "Operation against a key holding the wrong kind of value")
+            }
+            Error::NoScript => write!(f, "No matching script. Please use EVAL."),
+            Error::Busy => write!(
+                f,
+                "Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."
+            ),
+            Error::NoAuth => write!(f, "Authentication required."),
+            Error::WrongPass => write!(f, "invalid username-password pair or user is disabled."),
+            Error::ReadOnly => write!(f, "You can't write against a read only replica."),
+            Error::Oom => write!(f, "command not allowed when used memory > 'maxmemory'."),
+            Error::ExecAbort => write!(f, "Transaction discarded because of previous errors."),
+            Error::NoProto => write!(f, "unsupported protocol version"),
+            Error::Loading => write!(f, "Redis is loading the dataset in memory"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
 
 #[derive(Debug, PartialEq)]
-pub struct String(pub std::string::String);
+pub struct Key(pub Vec<u8>);
+
+#[derive(Debug, PartialEq)]
+pub struct String(pub Vec<u8>);
 
 #[derive(Debug, PartialEq)]
 pub struct Integer(pub i64);
@@ -31,6 +145,74 @@ pub enum SetCondition {
     IfExists,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ExpireCondition {
+    IfNoExpiry,
+    IfExpiry,
+    IfGreater,
+    IfLess,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientType {
+    Normal,
+    Master,
+    Replica,
+    PubSub,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientAttribute {
+    LibName,
+    LibVer,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KillFilter {
+    Id(Integer),
+    Type(ClientType),
+    User(String),
+    Addr(String),
+    LAddr(String),
+    SkipMe(bool),
+    MaxAge(Integer),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PauseMode {
+    Write,
+    All,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientCommand {
+    Id,
+    Info,
+    List {
+        kind: Option<ClientType>,
+        ids: Vec<Integer>,
+    },
+    SetName {
+        name: String,
+    },
+    GetName,
+    SetInfo {
+        attribute: ClientAttribute,
+        value: String,
+    },
+    KillAddr {
+        addr: String,
+    },
+    Kill {
+        filters: Vec<KillFilter>,
+    },
+    Pause {
+        timeout: Integer,
+        mode: PauseMode,
+    },
+    Unpause,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Command {
     Get {
@@ -43,13 +225,101 @@ pub enum Command {
         get: bool,
         condition: Option<SetCondition>,
     },
-    Client,
+    GetDel {
+        key: Key,
+    },
+    GetEx {
+        key: Key,
+        expiration: Option<Expiration>,
+        persist: bool,
+    },
+    SetEx {
+        key: Key,
+        value: String,
+        expiration: Expiration,
+    },
+    SetNx {
+        key: Key,
+        value: String,
+    },
+    MGet {
+        keys: Vec<Key>,
+    },
+    MSet {
+        pairs: Vec<(Key, String)>,
+    },
+    MSetNx {
+        pairs: Vec<(Key, String)>,
+    },
+    GetRange {
+        key: Key,
+        start: Integer,
+        end: Integer,
+    },
+    SetRange {
+        key: Key,
+        offset: Integer,
+        value: String,
+    },
+    Lcs {
+        key1: Key,
+        key2: Key,
+        len: bool,
+        idx: bool,
+        min_match_len: Integer,
+        with_match_len: bool,
+    },
+    Client(ClientCommand),
+    CommandCount,
+    CommandInfo {
+        names: Vec<String>,
+    },
+    CommandDocs {
+        names: Vec<String>,
+    },
+    CommandGetKeys {
+        args: Vec<String>,
+    },
+    ConfigGet {
+        patterns: Vec<String>,
+    },
+    ConfigSet {
+        parameters: Vec<(String, String)>,
+    },
+    ConfigRewrite,
+    ConfigResetStat,
+    Ping {
+        message: Option<String>,
+    },
+    Hello {
+        protocol: Option<Integer>,
+        // a username and password
+        auth: Option<(String, String)>,
+        setname: Option<String>,
+    },
     Incr {
         key: Key,
+        increment: Integer,
+    },
+    IncrByFloat {
+        key: Key,
+        increment: f64,
     },
     Ttl {
         key: Key,
     },
+    PTtl {
+        key: Key,
+    },
+    ExpireTime {
+        key: Key,
+    },
+    PExpireTime {
+        key: Key,
+    },
+    Persist {
+        key: Key,
+    },
     Append {
         key: Key,
         value: String,
@@ -59,7 +329,45 @@ pub enum Command {
     },
     Expire {
         key: Key,
-        seconds: Integer,
+        expiration: Expiration,
+        conditions: Vec<ExpireCondition>,
+    },
+    Del {
+        keys: Vec<Key>,
+    },
+    Unlink {
+        keys: Vec<Key>,
+    },
+    Exists {
+        keys: Vec<Key>,
+    },
+    Type {
+        key: Key,
+    },
+    Rename {
+        key: Key,
+        new_key: Key,
+    },
+    RenameNx {
+        key: Key,
+        new_key: Key,
+    },
+    Copy {
+        source: Key,
+        destination: Key,
+        replace: bool,
+    },
+    Touch {
+        keys: Vec<Key>,
+    },
+    Keys {
+        pattern: String,
+    },
+    Scan {
+        cursor: u64,
+        pattern: Option<String>,
+        count: Integer,
+        kind: Option<String>,
     },
 }
 
@@ -1,100 +1,751 @@
-use futures::io::{AsyncBufRead, AsyncWrite};
+use futures::io::{AsyncBufRead, AsyncRead, AsyncWrite};
 use futures_lite::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    pub fn version(&self) -> i64 {
+        match self {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Value {
     SimpleString(String),
-    BulkString(String),
+    BulkString(Vec<u8>),
     Array(Vec<Value>),
     Error(String),
     Null,
+    NullArray,
     Integer(i64),
+    Map(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    VerbatimString {
+        format: String,
+        text: String,
+    },
+    Push(Vec<Value>),
+    Attribute {
+        attributes: Vec<(Value, Value)>,
+        value: Box<Value>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_bulk_len: usize,
+    pub max_multibulk_len: usize,
+    pub max_inline_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: i32::MAX as usize,
+            max_inline_len: 64 * 1024,
+        }
+    }
+}
+
+const MAX_PREALLOCATION: usize = 1024;
+
+const READ_SIZE: usize = 16 * 1024;
+
+/// Incrementally decodes client requests (multibulk or inline) out of a
+/// growable buffer. Decoding can stop at any byte and resume once more data
+/// has been read, and decoded arguments are handed out as slices of the
+/// buffer itself.
+pub struct Decoder {
+    buffer: Vec<u8>,
+    frame_start: usize,
+    position: usize,
+    state: State,
+    args: Vec<std::ops::Range<usize>>,
+    inline: Vec<u8>,
+    limits: Limits,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Start,
+    Multibulk { remaining: usize },
+    Bulk { remaining: usize, len: usize },
+}
+
+pub struct Request<'a> {
+    buffer: &'a [u8],
+    args: &'a [std::ops::Range<usize>],
+}
+
+impl<'a> Request<'a> {
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn args(&self) -> impl ExactSizeIterator<Item = &'a [u8]> + use<'a> {
+        let buffer = self.buffer;
+        self.args.iter().map(move |r| &buffer[r.clone()])
+    }
+}
+
+impl Decoder {
+    pub fn new(limits: Limits) -> Self {
+        Decoder {
+            buffer: Vec::with_capacity(READ_SIZE),
+            frame_start: 0,
+            position: 0,
+            state: State::Start,
+            args: Vec::new(),
+            inline: Vec::new(),
+            limits,
+        }
+    }
+
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.compact();
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub async fn read_from<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> std::io::Result<usize> {
+        self.compact();
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_SIZE, 0);
+        let read = reader.read(&mut self.buffer[len..]).await;
+        self.buffer.truncate(len + *read.as_ref().unwrap_or(&0));
+        read
+    }
+
+    pub fn has_buffered_data(&self) -> bool {
+        self.position < self.buffer.len()
+    }
+
+    // Drops every byte before the frame currently being decoded, so that the
+    // buffer only grows as much as the largest request.
+    fn compact(&mut self) {
+        if self.frame_start == 0 {
+            return;
+        }
+        self.buffer.drain(..self.frame_start);
+        self.position -= self.frame_start;
+        for arg in self.args.iter_mut() {
+            arg.start -= self.frame_start;
+            arg.end -= self.frame_start;
+        }
+        self.frame_start = 0;
+    }
+
+    pub fn decode(&mut self) -> std::io::Result<Option<Request<'_>>> {
+        let limits = self.limits;
+        loop {
+            match self.state {
+                State::Start => {
+                    self.frame_start = self.position;
+                    self.args.clear();
+                    match self.buffer.get(self.position) {
+                        None => return Ok(None),
+                        Some(b'*') => {
+                            let Some(line) = self.line(1, "too big mbulk count string")? else {
+                                return Ok(None);
+                            };
+                            match parse_len(
+                                line,
+                                limits.max_multibulk_len,
+                                "invalid multibulk length",
+                            )? {
+                                None | Some(0) => {}
+                                Some(remaining) => self.state = State::Multibulk { remaining },
+                            }
+                        }
+                        Some(_) => {
+                            let Some(line) = self.line(0, "too big inline request")? else {
+                                return Ok(None);
+                            };
+                            let line = line.strip_suffix(b"\r").unwrap_or(line);
+                            let args = split_args(line)
+                                .ok_or_else(|| invalid_data("unbalanced quotes in request"))?;
+                            if args.is_empty() {
+                                continue;
+                            }
+                            self.inline.clear();
+                            for arg in args {
+                                let start = self.inline.len();
+                                self.inline.extend_from_slice(&arg);
+                                self.args.push(start..self.inline.len());
+                            }
+                            return Ok(Some(Request {
+                                buffer: &self.inline,
+                                args: &self.args,
+                            }));
+                        }
+                    }
+                }
+                State::Multibulk { remaining: 0 } => {
+                    self.state = State::Start;
+                    return Ok(Some(Request {
+                        buffer: &self.buffer,
+                        args: &self.args,
+                    }));
+                }
+                State::Multibulk { remaining } => {
+                    match self.buffer.get(self.position) {
+                        None => return Ok(None),
+                        Some(b'$') => {}
+                        Some(c) => {
+                            return Err(invalid_data(&format!(
+                                "expected '$', got '{}'",
+                                c.escape_ascii()
+                            )));
+                        }
+                    }
+                    let Some(line) = self.line(1, "too big bulk count string")? else {
+                        return Ok(None);
+                    };
+                    let len = parse_len(line, limits.max_bulk_len, "invalid bulk length")?
+                        .ok_or_else(|| invalid_data("invalid bulk length"))?;
+                    self.state = State::Bulk { remaining, len };
+                }
+                State::Bulk { remaining, len } => {
+                    let start = self.position;
+                    let end = start + len;
+                    match self.buffer.get(end..end + 2) {
+                        None => return Ok(None),
+                        Some(b"\r\n") => {}
+                        Some(_) => return Err(invalid_data("invalid line terminator")),
+                    }
+                    self.args.push(start..end);
+                    self.position = end + 2;
+                    self.state = State::Multibulk {
+                        remaining: remaining - 1,
+                    };
+                }
+            }
+        }
+    }
+
+    // Returns the next line, skipping `skip` prefix bytes and excluding the
+    // terminating `\n`, or `None` if it has not been fully buffered yet.
+    fn line(&mut self, skip: usize, too_big: &str) -> std::io::Result<Option<&[u8]>> {
+        let start = self.position;
+        let available = &self.buffer[start..];
+        match available.iter().position(|c| *c == b'\n') {
+            Some(end) if end <= self.limits.max_inline_len => {
+                self.position = start + end + 1;
+                Ok(Some(&self.buffer[start + skip..start + end]))
+            }
+            None if available.len() <= self.limits.max_inline_len => Ok(None),
+            _ => Err(invalid_data(too_big)),
+        }
+    }
+}
+
+fn parse_len(line: &[u8], max_len: usize, error: &str) -> std::io::Result<Option<usize>> {
+    let Some(digits) = line.strip_suffix(b"\r") else {
+        return Err(invalid_data("invalid line terminator"));
+    };
+    match std::str::from_utf8(digits)
+        .ok()
+        .and_then(|d| d.parse::<i64>().ok())
+    {
+        Some(-1) => Ok(None),
+        Some(len) => usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= max_len)
+            .map(Some)
+            .ok_or_else(|| invalid_data(error)),
+        None => Err(invalid_data(error)),
+    }
 }
 
 pub async fn parse<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Value> {
+    parse_with_limits(reader, &Limits::default()).await
+}
+
+pub async fn parse_with_limits<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<Value> {
     let mut prefix = [0];
     reader.read_exact(&mut prefix).await?;
-    if &prefix == b"*" {
-        let len = parse_length(reader).await?;
-        let mut values = Vec::with_capacity(len);
-        for _ in 0..len {
-            values.push(Box::pin(parse(reader)).await?);
-        }
-        Ok(Value::Array(values))
-    } else if &prefix == b"$" {
-        let len = parse_length(reader).await?;
-        let string = parse_string(reader, len).await?;
-        Ok(Value::BulkString(string))
+    match &prefix {
+        b"+" => Ok(Value::SimpleString(parse_text(reader, limits).await?)),
+        b"-" => Ok(Value::Error(parse_text(reader, limits).await?)),
+        b":" => Ok(Value::Integer(parse_integer(reader, limits).await?)),
+        b"$" => match parse_bulk_length(reader, limits).await? {
+            Some(len) => Ok(Value::BulkString(parse_bytes(reader, len).await?)),
+            None => Ok(Value::Null),
+        },
+        b"*" => match parse_multibulk_length(reader, limits).await? {
+            Some(len) => Ok(Value::Array(parse_values(reader, len, limits).await?)),
+            None => Ok(Value::NullArray),
+        },
+        b"_" => {
+            parse_line(reader, limits).await?;
+            Ok(Value::Null)
+        }
+        b"," => parse_text(reader, limits)
+            .await?
+            .parse()
+            .map(Value::Double)
+            .map_err(|_| invalid_data("invalid double")),
+        b"#" => match parse_line(reader, limits).await?.as_slice() {
+            b"t" => Ok(Value::Boolean(true)),
+            b"f" => Ok(Value::Boolean(false)),
+            _ => Err(invalid_data("invalid boolean")),
+        },
+        b"(" => Ok(Value::BigNumber(parse_text(reader, limits).await?)),
+        b"!" => {
+            let len = parse_bulk_length(reader, limits)
+                .await?
+                .ok_or_else(|| invalid_data("invalid bulk length"))?;
+            let bytes = parse_bytes(reader, len).await?;
+            String::from_utf8(bytes)
+                .map(Value::Error)
+                .map_err(|_| invalid_data("invalid blob error"))
+        }
+        b"=" => {
+            let len = parse_bulk_length(reader, limits)
+                .await?
+                .ok_or_else(|| invalid_data("invalid bulk length"))?;
+            let bytes = parse_bytes(reader, len).await?;
+            let string = String::from_utf8(bytes).map_err(|_| invalid_data("invalid string"))?;
+            match string.split_once(':') {
+                Some((format, text)) if format.len() == 3 => Ok(Value::VerbatimString {
+                    format: format.to_string(),
+                    text: text.to_string(),
+                }),
+                _ => Err(invalid_data("invalid verbatim string")),
+            }
+        }
+        b"%" => {
+            let len = parse_aggregate_length(reader, limits).await?;
+            Ok(Value::Map(parse_pairs(reader, len, limits).await?))
+        }
+        b"~" => {
+            let len = parse_aggregate_length(reader, limits).await?;
+            Ok(Value::Set(parse_values(reader, len, limits).await?))
+        }
+        b">" => {
+            let len = parse_aggregate_length(reader, limits).await?;
+            Ok(Value::Push(parse_values(reader, len, limits).await?))
+        }
+        b"|" => {
+            let len = parse_aggregate_length(reader, limits).await?;
+            let attributes = parse_pairs(reader, len, limits).await?;
+            let value = Box::new(Box::pin(parse_with_limits(reader, limits)).await?);
+            Ok(Value::Attribute { attributes, value })
+        }
+        _ => Err(invalid_data(&format!(
+            "invalid prefix '{}'",
+            prefix[0].escape_ascii()
+        ))),
+    }
+}
+
+async fn parse_values<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    len: usize,
+    limits: &Limits,
+) -> std::io::Result<Vec<Value>> {
+    let mut values = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    for _ in 0..len {
+        values.push(Box::pin(parse_with_limits(reader, limits)).await?);
+    }
+    Ok(values)
+}
+
+async fn parse_pairs<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    len: usize,
+    limits: &Limits,
+) -> std::io::Result<Vec<(Value, Value)>> {
+    let mut pairs = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    for _ in 0..len {
+        let key = Box::pin(parse_with_limits(reader, limits)).await?;
+        let value = Box::pin(parse_with_limits(reader, limits)).await?;
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+// Splits an inline command the same way Redis' `sdssplitargs` does: arguments
+// are separated by whitespace and can be wrapped in double quotes (supporting
+// `\n`, `\r`, `\t`, `\b`, `\a` and `\xHH` escapes) or single quotes (only
+// supporting `\'`). A closing quote must be followed by whitespace.
+pub(crate) fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while line.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Some(args);
+        }
+        let mut arg = Vec::new();
+        let mut in_double_quotes = false;
+        let mut in_single_quotes = false;
+        loop {
+            let c = line.get(i).copied();
+            if in_double_quotes {
+                match c? {
+                    b'\\'
+                        if line.get(i + 1) == Some(&b'x')
+                            && line.get(i + 2).is_some_and(u8::is_ascii_hexdigit)
+                            && line.get(i + 3).is_some_and(u8::is_ascii_hexdigit) =>
+                    {
+                        arg.push(hex_value(line[i + 2]) << 4 | hex_value(line[i + 3]));
+                        i += 3;
+                    }
+                    b'\\' if i + 1 < line.len() => {
+                        i += 1;
+                        arg.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            c => c,
+                        });
+                    }
+                    b'"' => {
+                        if line.get(i + 1).is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    }
+                    c => arg.push(c),
+                }
+            } else if in_single_quotes {
+                match c? {
+                    b'\\' if line.get(i + 1) == Some(&b'\'') => {
+                        i += 1;
+                        arg.push(b'\'');
+                    }
+                    b'\'' => {
+                        if line.get(i + 1).is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        i += 1;
+                        break;
+                    }
+                    c => arg.push(c),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double_quotes = true,
+                    Some(b'\'') => in_single_quotes = true,
+                    Some(c) => arg.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
+fn hex_value(c: u8) -> u8 {
+    (c as char).to_digit(16).unwrap_or(0) as u8
+}
+
+// Reads up to `max_len` bytes looking for a `\n`, returning the line without
+// it, or `None` if the line is longer than that.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_len: usize,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let limit = max_len.saturating_add(1) as u64;
+    (&mut *reader)
+        .take(limit)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if line.pop() == Some(b'\n') {
+        Ok(Some(line))
+    } else if line.len() >= max_len {
+        Ok(None)
     } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "invalid prefix",
-        ))
+        Err(std::io::ErrorKind::UnexpectedEof.into())
     }
 }
 
-async fn parse_length<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<usize> {
-    let mut len_str = String::new();
-    reader.read_line(&mut len_str).await?;
-    len_str.truncate(len_str.len() - 2);
-    len_str
+async fn parse_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<Vec<u8>> {
+    let mut line = read_line(reader, limits.max_inline_len)
+        .await?
+        .ok_or_else(|| invalid_data("too big line"))?;
+    if line.pop() != Some(b'\r') {
+        return Err(invalid_data("invalid line terminator"));
+    }
+    Ok(line)
+}
+
+async fn parse_text<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<String> {
+    String::from_utf8(parse_line(reader, limits).await?).map_err(|_| invalid_data("invalid string"))
+}
+
+async fn parse_integer<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<i64> {
+    parse_text(reader, limits)
+        .await?
         .parse()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid length"))
+        .map_err(|_| invalid_data("invalid integer"))
+}
+
+async fn parse_length<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+    max_len: usize,
+    error: &str,
+) -> std::io::Result<Option<usize>> {
+    match parse_text(reader, limits).await?.parse::<i64>() {
+        Ok(-1) => Ok(None),
+        Ok(len) => usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= max_len)
+            .map(Some)
+            .ok_or_else(|| invalid_data(error)),
+        Err(_) => Err(invalid_data(error)),
+    }
+}
+
+async fn parse_bulk_length<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<Option<usize>> {
+    parse_length(reader, limits, limits.max_bulk_len, "invalid bulk length").await
+}
+
+async fn parse_multibulk_length<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<Option<usize>> {
+    parse_length(
+        reader,
+        limits,
+        limits.max_multibulk_len,
+        "invalid multibulk length",
+    )
+    .await
+}
+
+async fn parse_aggregate_length<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+) -> std::io::Result<usize> {
+    parse_multibulk_length(reader, limits)
+        .await?
+        .ok_or_else(|| invalid_data("invalid multibulk length"))
 }
 
-async fn parse_string<R: AsyncBufRead + Unpin>(
+async fn parse_bytes<R: AsyncBufRead + Unpin>(
     reader: &mut R,
     length: usize,
-) -> std::io::Result<String> {
-    let mut string = vec![0; length];
-    reader.read_exact(&mut string).await?;
-    reader.read_exact(&mut [0; 2]).await?;
-    String::from_utf8(string)
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid bulk string"))
+) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(length.min(MAX_PREALLOCATION * MAX_PREALLOCATION));
+    (&mut *reader)
+        .take(length as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    if bytes.len() < length {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    let mut terminator = [0; 2];
+    reader.read_exact(&mut terminator).await?;
+    if &terminator != b"\r\n" {
+        return Err(invalid_data("invalid line terminator"));
+    }
+    Ok(bytes)
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
 }
 
 pub async fn serialise<W: AsyncWrite + Unpin>(
     writer: &mut W,
     value: &Value,
+    protocol: Protocol,
 ) -> std::io::Result<()> {
     match value {
         Value::SimpleString(s) => {
-            writer.write_all(b"+").await?;
-            writer.write_all(s.as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
+            write_line(writer, b"+", s.as_bytes()).await?;
         }
         Value::Error(e) => {
-            writer.write_all(b"-").await?;
-            writer.write_all(e.as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
+            write_line(writer, b"-", e.as_bytes()).await?;
         }
         Value::BulkString(s) => {
-            writer.write_all(b"$").await?;
-            writer.write_all(s.len().to_string().as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
-            writer.write_all(s.as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
+            write_blob(writer, b"$", s).await?;
         }
         Value::Array(a) => {
-            writer.write_all(b"*").await?;
-            writer.write_all(a.len().to_string().as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
-            for item in a {
-                Box::pin(serialise(writer, item)).await?;
+            write_aggregate(writer, b"*", a, protocol).await?;
+        }
+        Value::Null => match protocol {
+            Protocol::Resp2 => writer.write_all(b"$-1\r\n").await?,
+            Protocol::Resp3 => writer.write_all(b"_\r\n").await?,
+        },
+        Value::NullArray => match protocol {
+            Protocol::Resp2 => writer.write_all(b"*-1\r\n").await?,
+            Protocol::Resp3 => writer.write_all(b"_\r\n").await?,
+        },
+        Value::Integer(i) => {
+            write_line(writer, b":", i.to_string().as_bytes()).await?;
+        }
+        Value::Map(m) => {
+            let prefix = match protocol {
+                Protocol::Resp2 => b"*",
+                Protocol::Resp3 => b"%",
+            };
+            let len = match protocol {
+                Protocol::Resp2 => m.len() * 2,
+                Protocol::Resp3 => m.len(),
+            };
+            write_line(writer, prefix, len.to_string().as_bytes()).await?;
+            for (k, v) in m {
+                Box::pin(serialise(writer, k, protocol)).await?;
+                Box::pin(serialise(writer, v, protocol)).await?;
             }
         }
-        Value::Null => {
-            writer.write_all(b"_\r\n").await?;
+        Value::Set(s) => {
+            let prefix = match protocol {
+                Protocol::Resp2 => b"*",
+                Protocol::Resp3 => b"~",
+            };
+            write_aggregate(writer, prefix, s, protocol).await?;
         }
-        Value::Integer(i) => {
-            writer.write_all(b":").await?;
-            writer.write_all(i.to_string().as_bytes()).await?;
-            writer.write_all(b"\r\n").await?;
+        Value::Double(d) => match protocol {
+            Protocol::Resp2 => write_blob(writer, b"$", format_double(*d).as_bytes()).await?,
+            Protocol::Resp3 => write_line(writer, b",", format_double(*d).as_bytes()).await?,
+        },
+        Value::Boolean(b) => match protocol {
+            Protocol::Resp2 => write_line(writer, b":", if *b { b"1" } else { b"0" }).await?,
+            Protocol::Resp3 => write_line(writer, b"#", if *b { b"t" } else { b"f" }).await?,
+        },
+        Value::BigNumber(n) => match protocol {
+            Protocol::Resp2 => write_blob(writer, b"$", n.as_bytes()).await?,
+            Protocol::Resp3 => write_line(writer, b"(", n.as_bytes()).await?,
+        },
+        Value::VerbatimString { format, text } => match protocol {
+            Protocol::Resp2 => write_blob(writer, b"$", text.as_bytes()).await?,
+            Protocol::Resp3 => {
+                write_blob(writer, b"=", format!("{}:{}", format, text).as_bytes()).await?
+            }
+        },
+        Value::Push(p) => {
+            let prefix = match protocol {
+                Protocol::Resp2 => b"*",
+                Protocol::Resp3 => b">",
+            };
+            write_aggregate(writer, prefix, p, protocol).await?;
+        }
+        Value::Attribute { attributes, value } => {
+            if protocol == Protocol::Resp3 {
+                write_line(writer, b"|", attributes.len().to_string().as_bytes()).await?;
+                for (k, v) in attributes {
+                    Box::pin(serialise(writer, k, protocol)).await?;
+                    Box::pin(serialise(writer, v, protocol)).await?;
+                }
+            }
+            Box::pin(serialise(writer, value, protocol)).await?;
         }
     }
     Ok(())
 }
 
+async fn write_line<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    prefix: &[u8],
+    line: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(prefix).await?;
+    writer.write_all(line).await?;
+    writer.write_all(b"\r\n").await
+}
+
+async fn write_blob<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    prefix: &[u8],
+    blob: &[u8],
+) -> std::io::Result<()> {
+    write_line(writer, prefix, blob.len().to_string().as_bytes()).await?;
+    writer.write_all(blob).await?;
+    writer.write_all(b"\r\n").await
+}
+
+async fn write_aggregate<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    prefix: &[u8],
+    values: &[Value],
+    protocol: Protocol,
+) -> std::io::Result<()> {
+    write_line(writer, prefix, values.len().to_string().as_bytes()).await?;
+    for value in values {
+        Box::pin(serialise(writer, value, protocol)).await?;
+    }
+    Ok(())
+}
+
+// Like Redis, which prints doubles with "%.17g": 17 significant digits,
+// without trailing zeros, and with an exponent only for very large or small
+// magnitudes.
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        return "nan".to_string();
+    } else if d.is_infinite() {
+        return if d > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let scientific = format!("{:.16e}", d);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if (-4..17).contains(&exponent) {
+        let fixed = format!("{:.*}", (16 - exponent) as usize, d);
+        trim_fraction(&fixed).to_string()
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}e{}{:02}",
+            trim_fraction(mantissa),
+            sign,
+            exponent.unsigned_abs()
+        )
+    }
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,7 +758,15 @@ mod tests {
         let mut bytes = b"$5\r\nHello\r\n".to_vec();
         let mut reader = Cursor::new(&mut bytes);
         let value = parse(&mut reader).await.unwrap();
-        assert_eq!(value, Value::BulkString("Hello".to_string()));
+        assert_eq!(value, Value::BulkString(b"Hello".to_vec()));
+    }
+
+    #[apply(test!)]
+    async fn test_parse_binary_bulk_string() {
+        let mut bytes = b"$4\r\n\xff\r\n\x00\r\n".to_vec();
+        let mut reader = Cursor::new(&mut bytes);
+        let value = parse(&mut reader).await.unwrap();
+        assert_eq!(value, Value::BulkString(b"\xff\r\n\x00".to_vec()));
     }
 
     #[apply(test!)]
@@ -118,17 +777,225 @@ mod tests {
         assert_eq!(
             value,
             Value::Array(vec![
-                Value::BulkString("Hello".to_string()),
-                Value::BulkString("World".to_string()),
+                Value::BulkString(b"Hello".to_vec()),
+                Value::BulkString(b"World".to_vec()),
             ])
         );
     }
 
+    #[apply(test!)]
+    async fn test_parse_resp2_types() {
+        let mut bytes = b"+OK\r\n-ERR oops\r\n:-42\r\n$-1\r\n*-1\r\n$0\r\n\r\n".to_vec();
+        let mut reader = Cursor::new(&mut bytes);
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::SimpleString("OK".to_string())
+        );
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::Error("ERR oops".to_string())
+        );
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::Integer(-42));
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::Null);
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::NullArray);
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::BulkString(vec![]));
+    }
+
+    #[apply(test!)]
+    async fn test_parse_resp3_types() {
+        let mut bytes =
+            b"_\r\n,1.5\r\n,-inf\r\n#t\r\n(12345678901234567890\r\n!3\r\nERR\r\n=7\r\ntxt:abc\r\n"
+                .to_vec();
+        let mut reader = Cursor::new(&mut bytes);
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::Null);
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::Double(1.5));
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::Double(f64::NEG_INFINITY)
+        );
+        assert_eq!(parse(&mut reader).await.unwrap(), Value::Boolean(true));
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::BigNumber("12345678901234567890".to_string())
+        );
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::Error("ERR".to_string())
+        );
+        assert_eq!(
+            parse(&mut reader).await.unwrap(),
+            Value::VerbatimString {
+                format: "txt".to_string(),
+                text: "abc".to_string(),
+            }
+        );
+    }
+
+    #[apply(test!)]
+    async fn test_parse_invalid() {
+        for bytes in [
+            &b"?\r\n"[..],
+            b":abc\r\n",
+            b"$3\r\nabcd\r\n",
+            b"+OK\n",
+            b"*-2\r\n",
+            b"#x\r\n",
+        ] {
+            let mut bytes = bytes.to_vec();
+            let mut reader = Cursor::new(&mut bytes);
+            let err = parse(&mut reader).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_and_parse_roundtrip() {
+        let value = Value::Push(vec![
+            Value::Map(vec![(
+                Value::SimpleString("key".to_string()),
+                Value::Set(vec![Value::Integer(1), Value::Boolean(false)]),
+            )]),
+            Value::Attribute {
+                attributes: vec![(Value::BulkString(b"ttl".to_vec()), Value::Double(0.5))],
+                value: Box::new(Value::Array(vec![
+                    Value::Null,
+                    Value::Error("ERR".to_string()),
+                ])),
+            },
+        ]);
+        let mut bytes = Vec::new();
+        serialise(&mut bytes, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        let mut reader = Cursor::new(&mut bytes);
+        assert_eq!(parse(&mut reader).await.unwrap(), value);
+    }
+
+    fn decode_all(decoder: &mut Decoder) -> std::io::Result<Vec<Vec<Vec<u8>>>> {
+        let mut requests = vec![];
+        while let Some(request) = decoder.decode()? {
+            requests.push(request.args().map(<[u8]>::to_vec).collect());
+        }
+        Ok(requests)
+    }
+
+    #[test]
+    fn test_decode_multibulk() {
+        let mut decoder = Decoder::new(Limits::default());
+        decoder.extend(b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$4\r\n\xff\r\n\x00\r\n");
+        assert_eq!(
+            decode_all(&mut decoder).unwrap(),
+            vec![
+                vec![b"PING".to_vec()],
+                vec![b"GET".to_vec(), b"\xff\r\n\x00".to_vec()]
+            ]
+        );
+        assert!(!decoder.has_buffered_data());
+    }
+
+    #[test]
+    fn test_decode_resumes_partial_frames() {
+        let request = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\nPING\r\n";
+        let mut decoder = Decoder::new(Limits::default());
+        let mut requests = vec![];
+        for byte in request {
+            decoder.extend(&[*byte]);
+            requests.extend(decode_all(&mut decoder).unwrap());
+        }
+        assert_eq!(
+            requests,
+            vec![
+                vec![b"SET".to_vec(), b"key".to_vec(), b"value".to_vec()],
+                vec![b"PING".to_vec()]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_inline() {
+        let mut decoder = Decoder::new(Limits::default());
+        decoder.extend(b"SET  key \"hello \\\"world\\\"\\x21\"\r\n  \r\n*0\r\nGET 'it\\'s'\n");
+        assert_eq!(
+            decode_all(&mut decoder).unwrap(),
+            vec![
+                vec![
+                    b"SET".to_vec(),
+                    b"key".to_vec(),
+                    b"hello \"world\"!".to_vec()
+                ],
+                vec![b"GET".to_vec(), b"it's".to_vec()]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_inline_unbalanced_quotes() {
+        for line in [&b"GET \"key\r\n"[..], b"GET \"key\"x\r\n", b"GET 'key\r\n"] {
+            let mut decoder = Decoder::new(Limits::default());
+            decoder.extend(line);
+            let err = decode_all(&mut decoder).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "unbalanced quotes in request");
+        }
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let limits = Limits {
+            max_bulk_len: 4,
+            max_multibulk_len: 2,
+            max_inline_len: 8,
+        };
+        for (request, error) in [
+            (&b"*3\r\n"[..], "invalid multibulk length"),
+            (b"*1\r\n$5\r\nHello\r\n", "invalid bulk length"),
+            (b"*1\r\n$-2\r\n", "invalid bulk length"),
+            (b"*1\r\n:1\r\n", "expected '$', got ':'"),
+            (b"*x\r\n", "invalid multibulk length"),
+            (b"GET some-key\r\n", "too big inline request"),
+            (b"GET some-key", "too big inline request"),
+            (b"*1\r\n$1\n", "invalid line terminator"),
+            (b"*1\r\n$1\r\nab\r\n", "invalid line terminator"),
+        ] {
+            let mut decoder = Decoder::new(limits);
+            decoder.extend(request);
+            let err = decode_all(&mut decoder).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), error);
+        }
+    }
+
+    #[apply(test!)]
+    async fn test_decoder_read_from() {
+        let mut bytes = b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nPI".to_vec();
+        let mut reader = Cursor::new(&mut bytes);
+        let mut decoder = Decoder::new(Limits::default());
+        assert_eq!(decoder.read_from(&mut reader).await.unwrap(), 24);
+        assert_eq!(
+            decode_all(&mut decoder).unwrap(),
+            vec![vec![b"PING".to_vec()]]
+        );
+        assert!(decoder.has_buffered_data());
+        assert_eq!(decoder.read_from(&mut reader).await.unwrap(), 0);
+    }
+
+    #[apply(test!)]
+    async fn test_parse_truncated() {
+        for bytes in [&b""[..], b"*", b"*1\r", b"$5\r\nHel"] {
+            let mut bytes = bytes.to_vec();
+            let mut reader = Cursor::new(&mut bytes);
+            let err = parse(&mut reader).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        }
+    }
+
     #[apply(test!)]
     async fn test_serialise_simple_string() {
         let mut writer = Vec::new();
         let value = Value::SimpleString("Hello".to_string());
-        serialise(&mut writer, &value).await.unwrap();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
         assert_eq!(writer, b"+Hello\r\n");
     }
 
@@ -136,15 +1003,19 @@ mod tests {
     async fn test_serialise_error() {
         let mut writer = Vec::new();
         let value = Value::Error("Hello".to_string());
-        serialise(&mut writer, &value).await.unwrap();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
         assert_eq!(writer, b"-Hello\r\n");
     }
 
     #[apply(test!)]
     async fn test_serialise_bulk_string() {
         let mut writer = Vec::new();
-        let value = Value::BulkString("Hello".to_string());
-        serialise(&mut writer, &value).await.unwrap();
+        let value = Value::BulkString(b"Hello".to_vec());
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
         assert_eq!(writer, b"$5\r\nHello\r\n");
     }
 
@@ -153,9 +1024,11 @@ mod tests {
         let mut writer = Vec::new();
         let value = Value::Array(vec![
             Value::SimpleString("Hello".to_string()),
-            Value::BulkString("World".to_string()),
+            Value::BulkString(b"World".to_vec()),
         ]);
-        serialise(&mut writer, &value).await.unwrap();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
         assert_eq!(writer, b"*2\r\n+Hello\r\n$5\r\nWorld\r\n");
     }
 
@@ -163,7 +1036,204 @@ mod tests {
     async fn test_serialise_null() {
         let mut writer = Vec::new();
         let value = Value::Null;
-        serialise(&mut writer, &value).await.unwrap();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"_\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_null_resp2() {
+        let mut writer = Vec::new();
+        let value = Value::Null;
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"$-1\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_null_array() {
+        let mut writer = Vec::new();
+        let value = Value::NullArray;
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
         assert_eq!(writer, b"_\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"*-1\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_map() {
+        let value = Value::Map(vec![(
+            Value::BulkString(b"key".to_vec()),
+            Value::Integer(42),
+        )]);
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"%1\r\n$3\r\nkey\r\n:42\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"*2\r\n$3\r\nkey\r\n:42\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_set() {
+        let value = Value::Set(vec![Value::Integer(1), Value::Integer(2)]);
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"~2\r\n:1\r\n:2\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"*2\r\n:1\r\n:2\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_double() {
+        let value = Value::Double(3.25);
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b",3.25\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"$4\r\n3.25\r\n");
+
+        let mut writer = Vec::new();
+        let value = Value::Double(f64::NEG_INFINITY);
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b",-inf\r\n");
+    }
+
+    #[test]
+    fn test_format_double() {
+        assert_eq!(format_double(3.25), "3.25");
+        assert_eq!(format_double(0.1), "0.10000000000000001");
+        assert_eq!(format_double(-2.0), "-2");
+        assert_eq!(format_double(0.0), "0");
+        assert_eq!(format_double(1e16), "10000000000000000");
+        assert_eq!(format_double(1e17), "1e+17");
+        assert_eq!(format_double(1.5e-5), "1.5e-05");
+        assert_eq!(format_double(1e-5), "1.0000000000000001e-05");
+        assert_eq!(format_double(123456789.123), "123456789.123");
+        assert_eq!(format_double(0.0001), "0.0001");
+        assert_eq!(format_double(f64::MAX), "1.7976931348623157e+308");
+        assert_eq!(format_double(f64::NAN), "nan");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_boolean() {
+        let mut writer = Vec::new();
+        serialise(&mut writer, &Value::Boolean(true), Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"#t\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &Value::Boolean(false), Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b":0\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_big_number() {
+        let value = Value::BigNumber("3492890328409238509324850943850943825024385".to_string());
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"(3492890328409238509324850943850943825024385\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(
+            writer,
+            b"$43\r\n3492890328409238509324850943850943825024385\r\n"
+        );
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_verbatim_string() {
+        let value = Value::VerbatimString {
+            format: "txt".to_string(),
+            text: "Some string".to_string(),
+        };
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"=15\r\ntxt:Some string\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"$11\r\nSome string\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_push() {
+        let value = Value::Push(vec![Value::BulkString(b"message".to_vec())]);
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b">1\r\n$7\r\nmessage\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"*1\r\n$7\r\nmessage\r\n");
+    }
+
+    #[apply(test!)]
+    async fn test_serialise_attribute() {
+        let value = Value::Attribute {
+            attributes: vec![(Value::SimpleString("ttl".to_string()), Value::Integer(3600))],
+            value: Box::new(Value::Integer(42)),
+        };
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp3)
+            .await
+            .unwrap();
+        assert_eq!(writer, b"|1\r\n+ttl\r\n:3600\r\n:42\r\n");
+
+        let mut writer = Vec::new();
+        serialise(&mut writer, &value, Protocol::Resp2)
+            .await
+            .unwrap();
+        assert_eq!(writer, b":42\r\n");
     }
 }
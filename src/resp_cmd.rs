@@ -1,64 +1,163 @@
-use std::collections::VecDeque;
-
+use crate::commands;
 use crate::redis;
 use crate::resp;
-use anyhow::{Result, anyhow};
+use crate::value;
+
+type Result<T> = std::result::Result<T, redis::Error>;
+
+trait Args<'a>: ExactSizeIterator<Item = &'a [u8]> {}
+
+impl<'a, I: ExactSizeIterator<Item = &'a [u8]>> Args<'a> for I {}
 
 pub fn parse_command(command: resp::Value) -> Result<redis::Command> {
-    let mut cmd = to_vec(command)?;
-    let cmd_name = cmd.pop_front().ok_or(anyhow!("command is empty"))?;
-    match cmd_name.to_uppercase().as_str() {
-        "GET" => get(&mut cmd),
-        "SET" => set(&mut cmd),
-        "INCR" => incr(&mut cmd),
-        "TTL" => ttl(&mut cmd),
-        "APPEND" => append(&mut cmd),
-        "STRLEN" => strlen(&mut cmd),
-        "EXPIRE" => expire(&mut cmd),
-        "CLIENT" => Ok(redis::Command::Client),
-        _ => Err(anyhow!("unknown command '{}'", cmd_name)),
-    }
-}
-
-fn get(args: &mut VecDeque<String>) -> Result<redis::Command> {
+    let args = to_vec(command)?;
+    parse_args(&mut args.iter().map(Vec::as_slice))
+}
+
+// Parses `request`, whose command the caller already looked up as `spec`.
+pub fn parse_request(
+    request: &resp::Request,
+    spec: Option<&'static commands::Spec>,
+) -> Result<redis::Command> {
+    let mut args = request.args();
+    let cmd_name = name(&mut args)?;
+    parse_spec(cmd_name, spec, &mut args)
+}
+
+fn parse_args<'a>(cmd: &mut impl Args<'a>) -> Result<redis::Command> {
+    let cmd_name = name(cmd)?;
+    parse_spec(cmd_name, commands::lookup(cmd_name), cmd)
+}
+
+fn name<'a>(cmd: &mut impl Args<'a>) -> Result<&'a [u8]> {
+    cmd.next()
+        .ok_or_else(|| redis::Error::Generic("command is empty".to_string()))
+}
+
+fn parse_spec<'a>(
+    cmd_name: &[u8],
+    spec: Option<&'static commands::Spec>,
+    cmd: &mut impl Args<'a>,
+) -> Result<redis::Command> {
+    let spec = spec.ok_or_else(|| unknown_command(cmd_name, cmd))?;
+    if !spec.accepts(cmd.len() + 1) {
+        return Err(redis::Error::WrongNumberOfArguments(spec.name.to_string()));
+    }
+    match uppercase(cmd_name, &mut [0; 16]) {
+        b"GET" => get(cmd),
+        b"SET" => set(cmd),
+        b"GETDEL" => Ok(redis::Command::GetDel { key: key(cmd)? }),
+        b"GETEX" => getex(cmd),
+        b"SETEX" => setex(cmd, "setex", redis::Expiration::Seconds),
+        b"PSETEX" => setex(cmd, "psetex", redis::Expiration::Milliseconds),
+        b"SETNX" => Ok(redis::Command::SetNx {
+            key: key(cmd)?,
+            value: string(cmd)?,
+        }),
+        b"GETRANGE" | b"SUBSTR" => getrange(cmd),
+        b"SETRANGE" => setrange(cmd),
+        b"LCS" => lcs(cmd),
+        b"MGET" => Ok(redis::Command::MGet { keys: keys(cmd) }),
+        b"MSET" => Ok(redis::Command::MSet {
+            pairs: pairs(cmd, spec)?,
+        }),
+        b"MSETNX" => Ok(redis::Command::MSetNx {
+            pairs: pairs(cmd, spec)?,
+        }),
+        b"INCR" => incr(cmd, Some(1)),
+        b"DECR" => incr(cmd, Some(-1)),
+        b"INCRBY" => incr(cmd, None),
+        b"DECRBY" => decrby(cmd),
+        b"INCRBYFLOAT" => incrbyfloat(cmd),
+        b"TTL" => ttl(cmd),
+        b"PTTL" => Ok(redis::Command::PTtl { key: key(cmd)? }),
+        b"EXPIRETIME" => Ok(redis::Command::ExpireTime { key: key(cmd)? }),
+        b"PEXPIRETIME" => Ok(redis::Command::PExpireTime { key: key(cmd)? }),
+        b"PERSIST" => Ok(redis::Command::Persist { key: key(cmd)? }),
+        b"APPEND" => append(cmd),
+        b"STRLEN" => strlen(cmd),
+        b"EXPIRE" => expire(cmd, redis::Expiration::Seconds),
+        b"PEXPIRE" => expire(cmd, redis::Expiration::Milliseconds),
+        b"EXPIREAT" => expire(cmd, redis::Expiration::UnixTimeSeconds),
+        b"PEXPIREAT" => expire(cmd, redis::Expiration::UnixTimeMilliseconds),
+        b"DEL" => Ok(redis::Command::Del { keys: keys(cmd) }),
+        b"UNLINK" => Ok(redis::Command::Unlink { keys: keys(cmd) }),
+        b"EXISTS" => Ok(redis::Command::Exists { keys: keys(cmd) }),
+        b"TYPE" => Ok(redis::Command::Type { key: key(cmd)? }),
+        b"RENAME" => rename(cmd),
+        b"RENAMENX" => renamenx(cmd),
+        b"COPY" => copy(cmd),
+        b"TOUCH" => Ok(redis::Command::Touch { keys: keys(cmd) }),
+        b"KEYS" => Ok(redis::Command::Keys {
+            pattern: string(cmd)?,
+        }),
+        b"SCAN" => scan(cmd),
+        b"CLIENT" => client(cmd),
+        b"COMMAND" => command(cmd),
+        b"CONFIG" => config(cmd),
+        b"HELLO" => hello(cmd),
+        b"PING" => ping(cmd),
+        _ => Err(unknown_command(cmd_name, cmd)),
+    }
+}
+
+// Mirrors Redis, which quotes the arguments until it has shown 128 bytes.
+fn unknown_command<'a>(name: &[u8], args: &mut impl Args<'a>) -> redis::Error {
+    let mut shown = Vec::new();
+    let mut len = 0;
+    for arg in args {
+        if len >= 128 {
+            break;
+        }
+        let arg = String::from_utf8_lossy(&arg[..arg.len().min(128 - len)]).into_owned();
+        len += arg.len() + 3;
+        shown.push(arg);
+    }
+    redis::Error::UnknownCommand {
+        name: String::from_utf8_lossy(name).into_owned(),
+        args: shown,
+    }
+}
+
+fn get<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
     Ok(redis::Command::Get { key })
 }
 
-fn set(args: &mut VecDeque<String>) -> Result<redis::Command> {
+fn set<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
     let value = string(args)?;
     let mut expiration = None;
     let mut get = false;
     let mut condition = None;
-    while let Some(arg) = args.pop_front() {
-        match arg.to_uppercase().as_str() {
-            "EX" => {
+    while let Some(arg) = args.next() {
+        match uppercase(arg, &mut [0; 16]) {
+            b"EX" => {
                 expiration = Some(redis::Expiration::Seconds(integer(args)?));
             }
-            "PX" => {
+            b"PX" => {
                 expiration = Some(redis::Expiration::Milliseconds(integer(args)?));
             }
-            "EXAT" => {
+            b"EXAT" => {
                 expiration = Some(redis::Expiration::UnixTimeSeconds(integer(args)?));
             }
-            "PXAT" => {
+            b"PXAT" => {
                 expiration = Some(redis::Expiration::UnixTimeMilliseconds(integer(args)?));
             }
-            "KEEPTTL" => {
+            b"KEEPTTL" => {
                 expiration = Some(redis::Expiration::Keep);
             }
-            "GET" => {
+            b"GET" => {
                 get = true;
             }
-            "NX" => {
+            b"NX" => {
                 condition = Some(redis::SetCondition::IfNotExists);
             }
-            "XX" => {
+            b"XX" => {
                 condition = Some(redis::SetCondition::IfExists);
             }
             _ => {
-                return Err(anyhow!("unexpected argument '{}'", arg));
+                return Err(redis::Error::Syntax);
             }
         }
     }
@@ -71,52 +170,537 @@ fn set(args: &mut VecDeque<String>) -> Result<redis::Command> {
     })
 }
 
-fn incr(args: &mut VecDeque<String>) -> Result<redis::Command> {
+// INCR and DECR have a fixed increment, INCRBY takes it as an argument.
+fn incr<'a>(args: &mut impl Args<'a>, increment: Option<i64>) -> Result<redis::Command> {
+    let key = key(args)?;
+    let increment = match increment {
+        Some(i) => redis::Integer(i),
+        None => integer(args)?,
+    };
+    Ok(redis::Command::Incr { key, increment })
+}
+
+fn decrby<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let key = key(args)?;
+    let redis::Integer(decrement) = integer(args)?;
+    let increment = decrement
+        .checked_neg()
+        .ok_or_else(|| redis::Error::Generic("decrement would overflow".to_string()))?;
+    Ok(redis::Command::Incr {
+        key,
+        increment: redis::Integer(increment),
+    })
+}
+
+fn incrbyfloat<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let key = key(args)?;
+    let increment = float(args)?;
+    Ok(redis::Command::IncrByFloat { key, increment })
+}
+
+fn getex<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let key = key(args)?;
+    let (expiration, persist) = match args.next() {
+        None => (None, false),
+        Some(option) => match uppercase(option, &mut [0; 16]) {
+            b"EX" => (Some(redis::Expiration::Seconds(integer(args)?)), false),
+            b"PX" => (Some(redis::Expiration::Milliseconds(integer(args)?)), false),
+            b"EXAT" => (
+                Some(redis::Expiration::UnixTimeSeconds(integer(args)?)),
+                false,
+            ),
+            b"PXAT" => (
+                Some(redis::Expiration::UnixTimeMilliseconds(integer(args)?)),
+                false,
+            ),
+            b"PERSIST" => (None, true),
+            _ => return Err(redis::Error::Syntax),
+        },
+    };
+    // only one option at a time
+    if args.next().is_some() {
+        return Err(redis::Error::Syntax);
+    }
+    Ok(redis::Command::GetEx {
+        key,
+        expiration,
+        persist,
+    })
+}
+
+// SETEX and PSETEX are SET with EX and PX, except that like in Redis their
+// expiration must be positive.
+fn setex<'a>(
+    args: &mut impl Args<'a>,
+    name: &'static str,
+    expiration: fn(redis::Integer) -> redis::Expiration,
+) -> Result<redis::Command> {
+    let key = key(args)?;
+    let amount = integer(args)?;
+    if amount.0 <= 0 {
+        return Err(redis::Error::InvalidExpireTime(name));
+    }
+    let value = string(args)?;
+    Ok(redis::Command::SetEx {
+        key,
+        value,
+        expiration: expiration(amount),
+    })
+}
+
+fn getrange<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
-    Ok(redis::Command::Incr { key })
+    let start = integer(args)?;
+    let end = integer(args)?;
+    Ok(redis::Command::GetRange { key, start, end })
 }
 
-fn ttl(args: &mut VecDeque<String>) -> Result<redis::Command> {
+fn setrange<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let key = key(args)?;
+    let offset = integer(args)?;
+    if offset.0 < 0 {
+        return Err(redis::Error::Generic("offset is out of range".to_string()));
+    }
+    let value = string(args)?;
+    Ok(redis::Command::SetRange { key, offset, value })
+}
+
+fn lcs<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let key1 = key(args)?;
+    let key2 = key(args)?;
+    let (mut len, mut idx, mut with_match_len) = (false, false, false);
+    let mut min_match_len = redis::Integer(0);
+    while let Some(option) = args.next() {
+        match uppercase(option, &mut [0; 16]) {
+            b"LEN" => len = true,
+            b"IDX" => idx = true,
+            b"WITHMATCHLEN" => with_match_len = true,
+            b"MINMATCHLEN" => min_match_len = redis::Integer(integer(args)?.0.max(0)),
+            _ => return Err(redis::Error::Syntax),
+        }
+    }
+    if len && idx {
+        return Err(redis::Error::Generic(
+            "If you want both the length and indexes, please just use IDX.".to_string(),
+        ));
+    }
+    Ok(redis::Command::Lcs {
+        key1,
+        key2,
+        len,
+        idx,
+        min_match_len,
+        with_match_len,
+    })
+}
+
+fn ttl<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
     Ok(redis::Command::Ttl { key })
 }
 
-fn append(args: &mut VecDeque<String>) -> Result<redis::Command> {
+fn append<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
     let value = string(args)?;
     Ok(redis::Command::Append { key, value })
 }
 
-fn strlen(args: &mut VecDeque<String>) -> Result<redis::Command> {
+fn strlen<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
     let key = key(args)?;
     Ok(redis::Command::Strlen { key })
 }
 
-fn expire(args: &mut VecDeque<String>) -> Result<redis::Command> {
+fn expire<'a>(
+    args: &mut impl Args<'a>,
+    expiration: fn(redis::Integer) -> redis::Expiration,
+) -> Result<redis::Command> {
     let key = key(args)?;
-    let seconds = integer(args)?;
-    Ok(redis::Command::Expire { key, seconds })
+    let expiration = expiration(integer(args)?);
+    let mut conditions = Vec::new();
+    for option in args {
+        conditions.push(match uppercase(option, &mut [0; 16]) {
+            b"NX" => redis::ExpireCondition::IfNoExpiry,
+            b"XX" => redis::ExpireCondition::IfExpiry,
+            b"GT" => redis::ExpireCondition::IfGreater,
+            b"LT" => redis::ExpireCondition::IfLess,
+            _ => {
+                return Err(redis::Error::Generic(format!(
+                    "Unsupported option {}",
+                    String::from_utf8_lossy(option)
+                )));
+            }
+        });
+    }
+    let has = |c| conditions.contains(&c);
+    if has(redis::ExpireCondition::IfNoExpiry)
+        && conditions
+            .iter()
+            .any(|c| c != &redis::ExpireCondition::IfNoExpiry)
+    {
+        return Err(redis::Error::Generic(
+            "NX and XX, GT or LT options at the same time are not compatible".to_string(),
+        ));
+    }
+    if has(redis::ExpireCondition::IfGreater) && has(redis::ExpireCondition::IfLess) {
+        return Err(redis::Error::Generic(
+            "GT and LT options at the same time are not compatible".to_string(),
+        ));
+    }
+    Ok(redis::Command::Expire {
+        key,
+        expiration,
+        conditions,
+    })
 }
 
-fn arg(args: &mut VecDeque<String>) -> Result<String> {
-    args.pop_front().ok_or(anyhow!("wrong number of arguments"))
+fn rename<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let (key, new_key) = (key(args)?, key(args)?);
+    Ok(redis::Command::Rename { key, new_key })
 }
 
-fn key(args: &mut VecDeque<String>) -> Result<redis::Key> {
-    arg(args).map(redis::Key)
+fn renamenx<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let (key, new_key) = (key(args)?, key(args)?);
+    Ok(redis::Command::RenameNx { key, new_key })
+}
+
+fn copy<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let source = key(args)?;
+    let destination = key(args)?;
+    let mut replace = false;
+    while let Some(arg) = args.next() {
+        match uppercase(arg, &mut [0; 16]) {
+            b"REPLACE" => replace = true,
+            // there is only one database
+            b"DB" => {
+                if integer(args)? != redis::Integer(0) {
+                    return Err(redis::Error::Generic(
+                        "DB index is out of range".to_string(),
+                    ));
+                }
+            }
+            _ => return Err(redis::Error::Syntax),
+        }
+    }
+    Ok(redis::Command::Copy {
+        source,
+        destination,
+        replace,
+    })
 }
 
-fn string(args: &mut VecDeque<String>) -> Result<redis::String> {
-    arg(args).map(redis::String)
+fn scan<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let cursor = std::str::from_utf8(arg(args)?)
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| redis::Error::Generic("invalid cursor".to_string()))?;
+    let mut pattern = None;
+    let mut count = redis::Integer(10);
+    let mut kind = None;
+    while let Some(option) = args.next() {
+        match uppercase(option, &mut [0; 16]) {
+            b"MATCH" => pattern = Some(string(args)?),
+            b"COUNT" => {
+                count = integer(args)?;
+                if count.0 < 1 {
+                    return Err(redis::Error::Syntax);
+                }
+            }
+            b"TYPE" => kind = Some(string(args)?),
+            _ => return Err(redis::Error::Syntax),
+        }
+    }
+    Ok(redis::Command::Scan {
+        cursor,
+        pattern,
+        count,
+        kind,
+    })
 }
 
-fn integer(args: &mut VecDeque<String>) -> Result<redis::Integer> {
+fn hello<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let protocol = if args.len() == 0 {
+        None
+    } else {
+        Some(integer(args).map_err(|_| {
+            redis::Error::Generic("Protocol version is not an integer or out of range".to_string())
+        })?)
+    };
+    let (mut auth, mut setname) = (None, None);
+    while let Some(option) = args.next() {
+        match uppercase(option, &mut [0; 16]) {
+            b"AUTH" if args.len() >= 2 => auth = Some((string(args)?, string(args)?)),
+            b"SETNAME" if args.len() >= 1 => setname = Some(string(args)?),
+            _ => {
+                return Err(redis::Error::Generic(format!(
+                    "Syntax error in HELLO option '{}'",
+                    String::from_utf8_lossy(option)
+                )));
+            }
+        }
+    }
+    Ok(redis::Command::Hello {
+        protocol,
+        auth,
+        setname,
+    })
+}
+
+fn command<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let Some(subcommand) = args.next() else {
+        return Ok(redis::Command::CommandInfo { names: vec![] });
+    };
+    let rest = args.map(|a| redis::String(a.to_vec())).collect::<Vec<_>>();
+    match uppercase(subcommand, &mut [0; 16]) {
+        b"COUNT" if rest.is_empty() => Ok(redis::Command::CommandCount),
+        b"INFO" => Ok(redis::Command::CommandInfo { names: rest }),
+        b"DOCS" => Ok(redis::Command::CommandDocs { names: rest }),
+        b"GETKEYS" if !rest.is_empty() => Ok(redis::Command::CommandGetKeys { args: rest }),
+        b"COUNT" | b"GETKEYS" => Err(redis::Error::WrongNumberOfArguments(format!(
+            "command|{}",
+            String::from_utf8_lossy(subcommand).to_lowercase()
+        ))),
+        _ => Err(redis::Error::UnknownSubcommand {
+            command: "COMMAND".to_string(),
+            subcommand: String::from_utf8_lossy(subcommand).into_owned(),
+        }),
+    }
+}
+
+fn client<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let subcommand = arg(args)?;
+    let wrong_arity = || {
+        redis::Error::WrongNumberOfArguments(format!(
+            "client|{}",
+            String::from_utf8_lossy(subcommand).to_lowercase()
+        ))
+    };
+    let command = match uppercase(subcommand, &mut [0; 16]) {
+        b"ID" if args.len() == 0 => redis::ClientCommand::Id,
+        b"INFO" if args.len() == 0 => redis::ClientCommand::Info,
+        b"LIST" => client_list(args)?,
+        b"SETNAME" if args.len() == 1 => redis::ClientCommand::SetName {
+            name: string(args)?,
+        },
+        b"GETNAME" if args.len() == 0 => redis::ClientCommand::GetName,
+        b"SETINFO" if args.len() == 2 => {
+            let option = arg(args)?;
+            let attribute = match uppercase(option, &mut [0; 16]) {
+                b"LIB-NAME" => redis::ClientAttribute::LibName,
+                b"LIB-VER" => redis::ClientAttribute::LibVer,
+                _ => {
+                    return Err(redis::Error::Generic(format!(
+                        "Unrecognized option '{}'",
+                        String::from_utf8_lossy(option)
+                    )));
+                }
+            };
+            redis::ClientCommand::SetInfo {
+                attribute,
+                value: string(args)?,
+            }
+        }
+        b"KILL" if args.len() == 1 => redis::ClientCommand::KillAddr {
+            addr: string(args)?,
+        },
+        b"KILL" if args.len() > 0 => client_kill(args)?,
+        b"PAUSE" if args.len() == 1 || args.len() == 2 => {
+            let timeout = integer(args)
+                .ok()
+                .filter(|redis::Integer(t)| *t >= 0)
+                .ok_or_else(|| {
+                    redis::Error::Generic("timeout is not an integer or out of range".to_string())
+                })?;
+            let mode = match args.next().map(|m| uppercase(m, &mut [0; 16]).to_vec()) {
+                None => redis::PauseMode::All,
+                Some(m) if m == b"ALL" => redis::PauseMode::All,
+                Some(m) if m == b"WRITE" => redis::PauseMode::Write,
+                Some(_) => return Err(redis::Error::Syntax),
+            };
+            redis::ClientCommand::Pause { timeout, mode }
+        }
+        b"UNPAUSE" if args.len() == 0 => redis::ClientCommand::Unpause,
+        b"ID" | b"INFO" | b"SETNAME" | b"GETNAME" | b"SETINFO" | b"KILL" | b"PAUSE"
+        | b"UNPAUSE" => return Err(wrong_arity()),
+        _ => {
+            return Err(redis::Error::UnknownSubcommand {
+                command: "CLIENT".to_string(),
+                subcommand: String::from_utf8_lossy(subcommand).into_owned(),
+            });
+        }
+    };
+    Ok(redis::Command::Client(command))
+}
+
+fn client_list<'a>(args: &mut impl Args<'a>) -> Result<redis::ClientCommand> {
+    let mut kind = None;
+    let mut ids = Vec::new();
+    while let Some(option) = args.next() {
+        match uppercase(option, &mut [0; 16]) {
+            b"TYPE" => kind = Some(client_type(arg(args)?)?),
+            b"ID" if args.len() > 0 => {
+                for id in args.by_ref() {
+                    let id = std::str::from_utf8(id)
+                        .ok()
+                        .and_then(|id| id.parse::<i64>().ok())
+                        .filter(|id| *id > 0)
+                        .ok_or_else(|| redis::Error::Generic("Invalid client ID".to_string()))?;
+                    ids.push(redis::Integer(id));
+                }
+            }
+            _ => return Err(redis::Error::Syntax),
+        }
+    }
+    Ok(redis::ClientCommand::List { kind, ids })
+}
+
+fn client_kill<'a>(args: &mut impl Args<'a>) -> Result<redis::ClientCommand> {
+    let mut filters = Vec::new();
+    while let Some(filter) = args.next() {
+        let value = arg(args)?;
+        let filter = match uppercase(filter, &mut [0; 16]) {
+            b"ID" => redis::KillFilter::Id(
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|id| id.parse::<i64>().ok())
+                    .filter(|id| *id > 0)
+                    .map(redis::Integer)
+                    .ok_or_else(|| {
+                        redis::Error::Generic("client-id should be greater than 0".to_string())
+                    })?,
+            ),
+            b"TYPE" => redis::KillFilter::Type(client_type(value)?),
+            b"USER" => redis::KillFilter::User(redis::String(value.to_vec())),
+            b"ADDR" => redis::KillFilter::Addr(redis::String(value.to_vec())),
+            b"LADDR" => redis::KillFilter::LAddr(redis::String(value.to_vec())),
+            b"SKIPME" => match uppercase(value, &mut [0; 16]) {
+                b"YES" => redis::KillFilter::SkipMe(true),
+                b"NO" => redis::KillFilter::SkipMe(false),
+                _ => return Err(redis::Error::Syntax),
+            },
+            b"MAXAGE" => redis::KillFilter::MaxAge(
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|age| age.parse().ok())
+                    .map(redis::Integer)
+                    .ok_or(redis::Error::NotAnInteger)?,
+            ),
+            _ => return Err(redis::Error::Syntax),
+        };
+        filters.push(filter);
+    }
+    Ok(redis::ClientCommand::Kill { filters })
+}
+
+fn client_type(kind: &[u8]) -> Result<redis::ClientType> {
+    match uppercase(kind, &mut [0; 16]) {
+        b"NORMAL" => Ok(redis::ClientType::Normal),
+        b"MASTER" => Ok(redis::ClientType::Master),
+        b"REPLICA" | b"SLAVE" => Ok(redis::ClientType::Replica),
+        b"PUBSUB" => Ok(redis::ClientType::PubSub),
+        _ => Err(redis::Error::Generic(format!(
+            "Unknown client type '{}'",
+            String::from_utf8_lossy(kind)
+        ))),
+    }
+}
+
+fn config<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let subcommand = arg(args)?;
+    let wrong_arity = || {
+        redis::Error::WrongNumberOfArguments(format!(
+            "config|{}",
+            String::from_utf8_lossy(subcommand).to_lowercase()
+        ))
+    };
+    match uppercase(subcommand, &mut [0; 16]) {
+        b"GET" if args.len() > 0 => Ok(redis::Command::ConfigGet {
+            patterns: args.map(|a| redis::String(a.to_vec())).collect(),
+        }),
+        b"SET" if args.len() > 0 && args.len().is_multiple_of(2) => {
+            let mut parameters = Vec::with_capacity(args.len() / 2);
+            while let (Some(name), Some(value)) = (args.next(), args.next()) {
+                parameters.push((redis::String(name.to_vec()), redis::String(value.to_vec())));
+            }
+            Ok(redis::Command::ConfigSet { parameters })
+        }
+        b"REWRITE" if args.len() == 0 => Ok(redis::Command::ConfigRewrite),
+        b"RESETSTAT" if args.len() == 0 => Ok(redis::Command::ConfigResetStat),
+        b"GET" | b"SET" | b"REWRITE" | b"RESETSTAT" => Err(wrong_arity()),
+        _ => Err(redis::Error::UnknownSubcommand {
+            command: "CONFIG".to_string(),
+            subcommand: String::from_utf8_lossy(subcommand).into_owned(),
+        }),
+    }
+}
+
+fn ping<'a>(args: &mut impl Args<'a>) -> Result<redis::Command> {
+    let message = args.next().map(|m| redis::String(m.to_vec()));
+    if args.len() != 0 {
+        return Err(redis::Error::WrongNumberOfArguments("ping".to_string()));
+    }
+    Ok(redis::Command::Ping { message })
+}
+
+fn arg<'a>(args: &mut impl Args<'a>) -> Result<&'a [u8]> {
+    args.next().ok_or(redis::Error::Syntax)
+}
+
+fn key<'a>(args: &mut impl Args<'a>) -> Result<redis::Key> {
+    arg(args).map(|k| redis::Key(k.to_vec()))
+}
+
+fn keys<'a>(args: &mut impl Args<'a>) -> Vec<redis::Key> {
+    args.map(|k| redis::Key(k.to_vec())).collect()
+}
+
+// The key-value pairs of MSET and MSETNX, which must come in full.
+fn pairs<'a>(
+    args: &mut impl Args<'a>,
+    spec: &commands::Spec,
+) -> Result<Vec<(redis::Key, redis::String)>> {
+    if !args.len().is_multiple_of(2) {
+        return Err(redis::Error::WrongNumberOfArguments(spec.name.to_string()));
+    }
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    while args.len() > 0 {
+        pairs.push((key(args)?, string(args)?));
+    }
+    Ok(pairs)
+}
+
+fn string<'a>(args: &mut impl Args<'a>) -> Result<redis::String> {
+    arg(args).map(|s| redis::String(s.to_vec()))
+}
+
+fn integer<'a>(args: &mut impl Args<'a>) -> Result<redis::Integer> {
     arg(args)
-        .and_then(|v| v.parse().map_err(|_| anyhow!("not an integer: {}", v)))
+        .and_then(|v| {
+            std::str::from_utf8(v)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or(redis::Error::NotAnInteger)
+        })
         .map(redis::Integer)
 }
 
-fn to_vec(value: resp::Value) -> Result<VecDeque<String>> {
+fn float<'a>(args: &mut impl Args<'a>) -> Result<f64> {
+    arg(args).and_then(|v| value::parse_float(v).ok_or(redis::Error::NotAFloat))
+}
+
+// Upper-cases a command name or option into `buffer` without allocating.
+// Arguments longer than the buffer cannot be keywords, so they map to nothing.
+fn uppercase<'b>(arg: &[u8], buffer: &'b mut [u8; 16]) -> &'b [u8] {
+    match buffer.get_mut(..arg.len()) {
+        Some(upper) => {
+            upper.copy_from_slice(arg);
+            upper.make_ascii_uppercase();
+            upper
+        }
+        None => &[],
+    }
+}
+
+fn to_vec(value: resp::Value) -> Result<Vec<Vec<u8>>> {
     if let resp::Value::Array(values) = value {
         values
             .into_iter()
@@ -124,14 +708,16 @@ fn to_vec(value: resp::Value) -> Result<VecDeque<String>> {
                 if let resp::Value::BulkString(s) = v {
                     Ok(s)
                 } else {
-                    Err(anyhow!(
-                        "invalid command: it should be an array of bulk strings",
+                    Err(redis::Error::Generic(
+                        "invalid command: it should be an array of bulk strings".to_string(),
                     ))
                 }
             })
-            .collect::<Result<VecDeque<_>>>()
+            .collect::<Result<Vec<_>>>()
     } else {
-        Err(anyhow!("invalid command: it should be an array",))
+        Err(redis::Error::Generic(
+            "invalid command: it should be an array".to_string(),
+        ))
     }
 }
 
@@ -140,8 +726,25 @@ pub fn serialise_result(result: redis::Result) -> resp::Value {
         redis::Result::BulkString(s) => resp::Value::BulkString(s),
         redis::Result::Null => resp::Value::Null,
         redis::Result::Ok => resp::Value::SimpleString("OK".to_string()),
+        redis::Result::SimpleString(s) => resp::Value::SimpleString(s),
         redis::Result::Integer(n) => resp::Value::Integer(n),
-        redis::Result::Error(e) => resp::Value::Error(e),
+        redis::Result::Error(e) => resp::Value::Error(e.to_string()),
+        redis::Result::Array(a) => {
+            resp::Value::Array(a.into_iter().map(serialise_result).collect())
+        }
+        redis::Result::Map(m) => resp::Value::Map(
+            m.into_iter()
+                .map(|(k, v)| (serialise_result(k), serialise_result(v)))
+                .collect(),
+        ),
+        redis::Result::Set(s) => resp::Value::Set(s.into_iter().map(serialise_result).collect()),
+        redis::Result::Double(d) => resp::Value::Double(d),
+        redis::Result::Boolean(b) => resp::Value::Boolean(b),
+        redis::Result::BigNumber(n) => resp::Value::BigNumber(n),
+        redis::Result::VerbatimString(s) => resp::Value::VerbatimString {
+            format: "txt".to_string(),
+            text: s,
+        },
     }
 }
 
@@ -150,17 +753,21 @@ mod tests {
     use super::*;
     use crate::redis::*;
 
+    fn parse(args: &[&[u8]]) -> std::result::Result<redis::Command, redis::Error> {
+        parse_args(&mut args.iter().copied())
+    }
+
     #[test]
     fn test_parse_command_get() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("GET".to_string()),
-            resp::Value::BulkString("key".to_string()),
+            resp::Value::BulkString(b"GET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Get {
-                key: Key("key".to_string()),
+                key: Key(b"key".to_vec()),
             }
         );
     }
@@ -168,16 +775,16 @@ mod tests {
     #[test]
     fn test_parse_command_set() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: None,
                 get: false,
                 condition: None,
@@ -188,18 +795,18 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_ex() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("EX".to_string()),
-            resp::Value::BulkString("3".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"EX".to_vec()),
+            resp::Value::BulkString(b"3".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: Some(Expiration::Seconds(Integer(3))),
                 get: false,
                 condition: None,
@@ -210,18 +817,18 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_px() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("PX".to_string()),
-            resp::Value::BulkString("300".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"PX".to_vec()),
+            resp::Value::BulkString(b"300".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: Some(Expiration::Milliseconds(Integer(300))),
                 get: false,
                 condition: None,
@@ -232,18 +839,18 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_exat() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("EXAT".to_string()),
-            resp::Value::BulkString("1749371595".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"EXAT".to_vec()),
+            resp::Value::BulkString(b"1749371595".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: Some(Expiration::UnixTimeSeconds(Integer(1749371595))),
                 get: false,
                 condition: None,
@@ -254,18 +861,18 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_pxat() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("PXAT".to_string()),
-            resp::Value::BulkString("1749371595123".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"PXAT".to_vec()),
+            resp::Value::BulkString(b"1749371595123".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: Some(Expiration::UnixTimeMilliseconds(Integer(1749371595123))),
                 get: false,
                 condition: None,
@@ -276,17 +883,17 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_keepttl() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("KEEPTTL".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"KEEPTTL".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: Some(Expiration::Keep),
                 get: false,
                 condition: None,
@@ -297,17 +904,17 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_get() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("GET".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"GET".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: None,
                 get: true,
                 condition: None,
@@ -318,17 +925,17 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_nx() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("NX".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"NX".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: None,
                 get: false,
                 condition: Some(redis::SetCondition::IfNotExists),
@@ -339,17 +946,17 @@ mod tests {
     #[test]
     fn test_parse_command_set_with_xx() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("SET".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
-            resp::Value::BulkString("XX".to_string()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"XX".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Set {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
                 expiration: None,
                 get: false,
                 condition: Some(redis::SetCondition::IfExists),
@@ -359,37 +966,485 @@ mod tests {
 
     #[test]
     fn test_parse_command_client() {
-        let command = resp::Value::Array(vec![resp::Value::BulkString("CLIENT".to_string())]);
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"SETNAME".to_vec()),
+            resp::Value::BulkString(b"name".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Client(redis::ClientCommand::SetName {
+                name: String(b"name".to_vec())
+            })
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"client".to_vec()),
+            resp::Value::BulkString(b"list".to_vec()),
+            resp::Value::BulkString(b"TYPE".to_vec()),
+            resp::Value::BulkString(b"normal".to_vec()),
+            resp::Value::BulkString(b"ID".to_vec()),
+            resp::Value::BulkString(b"1".to_vec()),
+            resp::Value::BulkString(b"2".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Client(redis::ClientCommand::List {
+                kind: Some(redis::ClientType::Normal),
+                ids: vec![redis::Integer(1), redis::Integer(2)]
+            })
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"KILL".to_vec()),
+            resp::Value::BulkString(b"ID".to_vec()),
+            resp::Value::BulkString(b"3".to_vec()),
+            resp::Value::BulkString(b"SKIPME".to_vec()),
+            resp::Value::BulkString(b"no".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Client(redis::ClientCommand::Kill {
+                filters: vec![
+                    redis::KillFilter::Id(redis::Integer(3)),
+                    redis::KillFilter::SkipMe(false)
+                ]
+            })
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"PAUSE".to_vec()),
+            resp::Value::BulkString(b"100".to_vec()),
+            resp::Value::BulkString(b"write".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Client(redis::ClientCommand::Pause {
+                timeout: redis::Integer(100),
+                mode: redis::PauseMode::Write
+            })
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"PAUSE".to_vec()),
+            resp::Value::BulkString(b"-1".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err().to_string(),
+            "ERR timeout is not an integer or out of range"
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"SETINFO".to_vec()),
+            resp::Value::BulkString(b"LIB-FOO".to_vec()),
+            resp::Value::BulkString(b"x".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err().to_string(),
+            "ERR Unrecognized option 'LIB-FOO'"
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CLIENT".to_vec()),
+            resp::Value::BulkString(b"GETNAME".to_vec()),
+            resp::Value::BulkString(b"extra".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err(),
+            Error::WrongNumberOfArguments("client|getname".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_command_hello() {
+        let command = resp::Value::Array(vec![resp::Value::BulkString(b"HELLO".to_vec())]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Hello {
+                protocol: None,
+                auth: None,
+                setname: None
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"HELLO".to_vec()),
+            resp::Value::BulkString(b"3".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Hello {
+                protocol: Some(Integer(3)),
+                auth: None,
+                setname: None
+            }
+        );
+
+        assert_eq!(
+            parse(&[
+                b"HELLO", b"3", b"auth", b"default", b"pass", b"SETNAME", b"app"
+            ]),
+            Ok(redis::Command::Hello {
+                protocol: Some(Integer(3)),
+                auth: Some((String(b"default".to_vec()), String(b"pass".to_vec()))),
+                setname: Some(String(b"app".to_vec())),
+            })
+        );
+        assert_eq!(
+            parse(&[b"HELLO", b"3", b"AUTH", b"default"]),
+            Err(redis::Error::Generic(
+                "Syntax error in HELLO option 'AUTH'".to_string()
+            ))
+        );
+        assert_eq!(
+            parse(&[b"HELLO", b"3", b"NOPE"]),
+            Err(redis::Error::Generic(
+                "Syntax error in HELLO option 'NOPE'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_command_command() {
+        let command = resp::Value::Array(vec![resp::Value::BulkString(b"COMMAND".to_vec())]);
         let parsed_command = parse_command(command).unwrap();
-        assert_eq!(parsed_command, redis::Command::Client);
+        assert_eq!(
+            parsed_command,
+            redis::Command::CommandInfo { names: vec![] }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COMMAND".to_vec()),
+            resp::Value::BulkString(b"count".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(parsed_command, redis::Command::CommandCount);
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COMMAND".to_vec()),
+            resp::Value::BulkString(b"DOCS".to_vec()),
+            resp::Value::BulkString(b"get".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::CommandDocs {
+                names: vec![String(b"get".to_vec())]
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COMMAND".to_vec()),
+            resp::Value::BulkString(b"GETKEYS".to_vec()),
+            resp::Value::BulkString(b"get".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::CommandGetKeys {
+                args: vec![String(b"get".to_vec()), String(b"key".to_vec())]
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COMMAND".to_vec()),
+            resp::Value::BulkString(b"FOO".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err().to_string(),
+            "ERR unknown subcommand 'FOO'. Try COMMAND HELP."
+        );
+    }
+
+    #[test]
+    fn test_parse_command_config() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CONFIG".to_vec()),
+            resp::Value::BulkString(b"get".to_vec()),
+            resp::Value::BulkString(b"max*".to_vec()),
+            resp::Value::BulkString(b"port".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::ConfigGet {
+                patterns: vec![String(b"max*".to_vec()), String(b"port".to_vec())]
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CONFIG".to_vec()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"maxclients".to_vec()),
+            resp::Value::BulkString(b"10".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::ConfigSet {
+                parameters: vec![(String(b"maxclients".to_vec()), String(b"10".to_vec()))]
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CONFIG".to_vec()),
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"maxclients".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err(),
+            Error::WrongNumberOfArguments("config|set".to_string())
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CONFIG".to_vec()),
+            resp::Value::BulkString(b"rewrite".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(parsed_command, redis::Command::ConfigRewrite);
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"CONFIG".to_vec()),
+            resp::Value::BulkString(b"RESETSTAT".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(parsed_command, redis::Command::ConfigResetStat);
+    }
+
+    #[test]
+    fn test_parse_command_ping() {
+        let command = resp::Value::Array(vec![resp::Value::BulkString(b"PING".to_vec())]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(parsed_command, redis::Command::Ping { message: None });
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"PING".to_vec()),
+            resp::Value::BulkString(b"hello".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Ping {
+                message: Some(String(b"hello".to_vec()))
+            }
+        );
     }
 
     #[test]
     fn test_parse_command_incr() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("INCR".to_string()),
-            resp::Value::BulkString("key".to_string()),
+            resp::Value::BulkString(b"INCR".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Incr {
-                key: Key("key".to_string())
+                key: Key(b"key".to_vec()),
+                increment: Integer(1)
             }
         );
     }
 
+    #[test]
+    fn test_parse_command_incr_family() {
+        let incr = |increment| redis::Command::Incr {
+            key: Key(b"key".to_vec()),
+            increment: Integer(increment),
+        };
+        assert_eq!(parse(&[b"DECR", b"key"]), Ok(incr(-1)));
+        assert_eq!(parse(&[b"INCRBY", b"key", b"5"]), Ok(incr(5)));
+        assert_eq!(parse(&[b"DECRBY", b"key", b"5"]), Ok(incr(-5)));
+        assert_eq!(
+            parse(&[b"DECRBY", b"key", b"-9223372036854775808"]),
+            Err(redis::Error::Generic(
+                "decrement would overflow".to_string()
+            ))
+        );
+        assert_eq!(
+            parse(&[b"INCRBY", b"key", b"1.5"]),
+            Err(redis::Error::NotAnInteger)
+        );
+
+        assert_eq!(
+            parse(&[b"INCRBYFLOAT", b"key", b"-1.5e2"]),
+            Ok(redis::Command::IncrByFloat {
+                key: Key(b"key".to_vec()),
+                increment: -150.0
+            })
+        );
+        for increment in [b"nan".as_slice(), b"inf", b" 1", b"1 ", b"one"] {
+            assert_eq!(
+                parse(&[b"INCRBYFLOAT", b"key", increment]),
+                Err(redis::Error::NotAFloat)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_command_mset() {
+        assert_eq!(
+            parse(&[b"MSET", b"a", b"1", b"b", b"2"]),
+            Ok(redis::Command::MSet {
+                pairs: vec![
+                    (Key(b"a".to_vec()), String(b"1".to_vec())),
+                    (Key(b"b".to_vec()), String(b"2".to_vec()))
+                ]
+            })
+        );
+        assert_eq!(
+            parse(&[b"MSETNX", b"a", b"1", b"b"]),
+            Err(redis::Error::WrongNumberOfArguments("msetnx".to_string()))
+        );
+        assert_eq!(
+            parse(&[b"MGET", b"a", b"b"]),
+            Ok(redis::Command::MGet {
+                keys: vec![Key(b"a".to_vec()), Key(b"b".to_vec())]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_command_getex() {
+        let getex = |expiration, persist| {
+            Ok(redis::Command::GetEx {
+                key: Key(b"key".to_vec()),
+                expiration,
+                persist,
+            })
+        };
+        assert_eq!(parse(&[b"GETEX", b"key"]), getex(None, false));
+        assert_eq!(
+            parse(&[b"GETEX", b"key", b"px", b"100"]),
+            getex(Some(Expiration::Milliseconds(Integer(100))), false)
+        );
+        assert_eq!(
+            parse(&[b"GETEX", b"key", b"EXAT", b"100"]),
+            getex(Some(Expiration::UnixTimeSeconds(Integer(100))), false)
+        );
+        assert_eq!(parse(&[b"GETEX", b"key", b"PERSIST"]), getex(None, true));
+        assert_eq!(
+            parse(&[b"GETEX", b"key", b"EX", b"1", b"PERSIST"]),
+            Err(redis::Error::Syntax)
+        );
+        assert_eq!(
+            parse(&[b"GETEX", b"key", b"KEEPTTL"]),
+            Err(redis::Error::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_parse_command_setex() {
+        assert_eq!(
+            parse(&[b"PSETEX", b"key", b"100", b"value"]),
+            Ok(redis::Command::SetEx {
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
+                expiration: Expiration::Milliseconds(Integer(100)),
+            })
+        );
+        assert_eq!(
+            parse(&[b"SETEX", b"key", b"0", b"value"]),
+            Err(redis::Error::InvalidExpireTime("setex"))
+        );
+        assert_eq!(
+            parse(&[b"SETNX", b"key", b"value"]),
+            Ok(redis::Command::SetNx {
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec())
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_command_ranges() {
+        let getrange = redis::Command::GetRange {
+            key: Key(b"key".to_vec()),
+            start: Integer(0),
+            end: Integer(-1),
+        };
+        assert_eq!(parse(&[b"GETRANGE", b"key", b"0", b"-1"]), Ok(getrange));
+        let substr = parse(&[b"SUBSTR", b"key", b"0", b"-1"]);
+        assert_eq!(substr, parse(&[b"GETRANGE", b"key", b"0", b"-1"]));
+
+        assert_eq!(
+            parse(&[b"SETRANGE", b"key", b"5", b"v"]),
+            Ok(redis::Command::SetRange {
+                key: Key(b"key".to_vec()),
+                offset: Integer(5),
+                value: String(b"v".to_vec())
+            })
+        );
+        assert_eq!(
+            parse(&[b"SETRANGE", b"key", b"-1", b"v"]),
+            Err(redis::Error::Generic("offset is out of range".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_command_lcs() {
+        assert_eq!(
+            parse(&[
+                b"LCS",
+                b"a",
+                b"b",
+                b"idx",
+                b"MINMATCHLEN",
+                b"-3",
+                b"WITHMATCHLEN"
+            ]),
+            Ok(redis::Command::Lcs {
+                key1: Key(b"a".to_vec()),
+                key2: Key(b"b".to_vec()),
+                len: false,
+                idx: true,
+                min_match_len: Integer(0),
+                with_match_len: true
+            })
+        );
+        assert_eq!(
+            parse(&[b"LCS", b"a", b"b", b"LEN", b"IDX"]),
+            Err(redis::Error::Generic(
+                "If you want both the length and indexes, please just use IDX.".to_string()
+            ))
+        );
+        assert_eq!(
+            parse(&[b"LCS", b"a", b"b", b"MINMATCHLEN"]),
+            Err(redis::Error::Syntax)
+        );
+        assert_eq!(
+            parse(&[b"LCS", b"a", b"b", b"NOPE"]),
+            Err(redis::Error::Syntax)
+        );
+    }
+
     #[test]
     fn test_parse_command_ttl() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("TTL".to_string()),
-            resp::Value::BulkString("key".to_string()),
+            resp::Value::BulkString(b"TTL".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Ttl {
-                key: Key("key".to_string())
+                key: Key(b"key".to_vec())
             }
         );
     }
@@ -397,16 +1452,16 @@ mod tests {
     #[test]
     fn test_parse_command_append() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("APPEND".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("value".to_string()),
+            resp::Value::BulkString(b"APPEND".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Append {
-                key: Key("key".to_string()),
-                value: String("value".to_string()),
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
             }
         );
     }
@@ -414,14 +1469,14 @@ mod tests {
     #[test]
     fn test_parse_command_strlen() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("STRLEN".to_string()),
-            resp::Value::BulkString("key".to_string()),
+            resp::Value::BulkString(b"STRLEN".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Strlen {
-                key: Key("key".to_string()),
+                key: Key(b"key".to_vec()),
             }
         );
     }
@@ -429,42 +1484,258 @@ mod tests {
     #[test]
     fn test_parse_command_expire() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("EXPIRE".to_string()),
-            resp::Value::BulkString("key".to_string()),
-            resp::Value::BulkString("42".to_string()),
+            resp::Value::BulkString(b"EXPIRE".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"42".to_vec()),
         ]);
         let parsed_command = parse_command(command).unwrap();
         assert_eq!(
             parsed_command,
             redis::Command::Expire {
-                key: Key("key".to_string()),
-                seconds: Integer(42),
+                key: Key(b"key".to_vec()),
+                expiration: Expiration::Seconds(Integer(42)),
+                conditions: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_command_expire_family() {
+        let parse = |args: &[&[u8]]| {
+            parse_command(resp::Value::Array(
+                args.iter()
+                    .map(|a| resp::Value::BulkString(a.to_vec()))
+                    .collect(),
+            ))
+        };
+
+        assert_eq!(
+            parse(&[b"pexpireat", b"key", b"-5", b"xx", b"GT"]),
+            Ok(redis::Command::Expire {
+                key: Key(b"key".to_vec()),
+                expiration: Expiration::UnixTimeMilliseconds(Integer(-5)),
+                conditions: vec![
+                    redis::ExpireCondition::IfExpiry,
+                    redis::ExpireCondition::IfGreater
+                ],
+            })
+        );
+        assert_eq!(
+            parse(&[b"PERSIST", b"key"]),
+            Ok(redis::Command::Persist {
+                key: Key(b"key".to_vec())
+            })
+        );
+        assert_eq!(
+            parse(&[b"EXPIRE", b"key", b"1", b"NX", b"XX"]),
+            Err(redis::Error::Generic(
+                "NX and XX, GT or LT options at the same time are not compatible".to_string()
+            ))
+        );
+        assert_eq!(
+            parse(&[b"EXPIREAT", b"key", b"1", b"GT", b"LT"]),
+            Err(redis::Error::Generic(
+                "GT and LT options at the same time are not compatible".to_string()
+            ))
+        );
+        assert_eq!(
+            parse(&[b"PEXPIRE", b"key", b"1", b"NEVER"]),
+            Err(redis::Error::Generic(
+                "Unsupported option NEVER".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let mut decoder = resp::Decoder::new(resp::Limits::default());
+        decoder.extend(b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+        let request = decoder.decode().unwrap().unwrap();
+        let parsed_command = parse_request(&request, commands::lookup(b"set")).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Set {
+                key: Key(b"key".to_vec()),
+                value: String(b"value".to_vec()),
+                expiration: None,
+                get: false,
+                condition: None,
             }
         );
     }
 
     #[test]
     fn test_parse_command_unknown() {
-        let command = resp::Value::Array(vec![resp::Value::BulkString("UNKNOWN".to_string())]);
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"UNKNOWN".to_vec()),
+            resp::Value::BulkString(b"foo".to_vec()),
+            resp::Value::BulkString(b"bar".to_vec()),
+        ]);
         let parsed_command = parse_command(command);
         assert!(parsed_command.is_err());
         assert_eq!(
             parsed_command.unwrap_err().to_string(),
-            "unknown command 'UNKNOWN'"
+            "ERR unknown command 'UNKNOWN', with args beginning with: 'foo' 'bar' "
         );
     }
 
     #[test]
     fn test_parse_command_not_enough_arguments() {
-        let command = resp::Value::Array(vec![resp::Value::BulkString("GET".to_string())]);
+        let command = resp::Value::Array(vec![resp::Value::BulkString(b"GET".to_vec())]);
         let parsed_command = parse_command(command);
         assert!(parsed_command.is_err());
+        assert_eq!(
+            parsed_command.unwrap_err(),
+            Error::WrongNumberOfArguments("get".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_command_too_many_arguments() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"GET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"extra".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err(),
+            Error::WrongNumberOfArguments("get".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_command_del() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"DEL".to_vec()),
+            resp::Value::BulkString(b"a".to_vec()),
+            resp::Value::BulkString(b"b".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Del {
+                keys: vec![Key(b"a".to_vec()), Key(b"b".to_vec())]
+            }
+        );
+
+        let command = resp::Value::Array(vec![resp::Value::BulkString(b"DEL".to_vec())]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err(),
+            Error::WrongNumberOfArguments("del".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_command_rename() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"RENAME".to_vec()),
+            resp::Value::BulkString(b"a".to_vec()),
+            resp::Value::BulkString(b"b".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Rename {
+                key: Key(b"a".to_vec()),
+                new_key: Key(b"b".to_vec())
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_command_copy() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COPY".to_vec()),
+            resp::Value::BulkString(b"a".to_vec()),
+            resp::Value::BulkString(b"b".to_vec()),
+            resp::Value::BulkString(b"DB".to_vec()),
+            resp::Value::BulkString(b"0".to_vec()),
+            resp::Value::BulkString(b"replace".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Copy {
+                source: Key(b"a".to_vec()),
+                destination: Key(b"b".to_vec()),
+                replace: true
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"COPY".to_vec()),
+            resp::Value::BulkString(b"a".to_vec()),
+            resp::Value::BulkString(b"b".to_vec()),
+            resp::Value::BulkString(b"DB".to_vec()),
+            resp::Value::BulkString(b"1".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
         assert_eq!(
             parsed_command.unwrap_err().to_string(),
-            "wrong number of arguments"
+            "ERR DB index is out of range"
         );
     }
 
+    #[test]
+    fn test_parse_command_scan() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"SCAN".to_vec()),
+            resp::Value::BulkString(b"0".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Scan {
+                cursor: 0,
+                pattern: None,
+                count: Integer(10),
+                kind: None
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"scan".to_vec()),
+            resp::Value::BulkString(b"18446744073709551615".to_vec()),
+            resp::Value::BulkString(b"match".to_vec()),
+            resp::Value::BulkString(b"user:*".to_vec()),
+            resp::Value::BulkString(b"COUNT".to_vec()),
+            resp::Value::BulkString(b"100".to_vec()),
+            resp::Value::BulkString(b"TYPE".to_vec()),
+            resp::Value::BulkString(b"string".to_vec()),
+        ]);
+        let parsed_command = parse_command(command).unwrap();
+        assert_eq!(
+            parsed_command,
+            redis::Command::Scan {
+                cursor: u64::MAX,
+                pattern: Some(String(b"user:*".to_vec())),
+                count: Integer(100),
+                kind: Some(String(b"string".to_vec()))
+            }
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"SCAN".to_vec()),
+            resp::Value::BulkString(b"-1".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err().to_string(),
+            "ERR invalid cursor"
+        );
+
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"SCAN".to_vec()),
+            resp::Value::BulkString(b"0".to_vec()),
+            resp::Value::BulkString(b"COUNT".to_vec()),
+            resp::Value::BulkString(b"0".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(parsed_command.unwrap_err(), Error::Syntax);
+    }
+
     #[test]
     fn test_parse_command_not_array() {
         let command = resp::Value::SimpleString("Hello".to_string());
@@ -472,29 +1743,55 @@ mod tests {
         assert!(parsed_command.is_err());
         assert_eq!(
             parsed_command.unwrap_err().to_string(),
-            "invalid command: it should be an array"
+            "ERR invalid command: it should be an array"
         );
     }
 
     #[test]
     fn test_parse_command_not_bulk_string_array() {
         let command = resp::Value::Array(vec![
-            resp::Value::BulkString("GET".to_string()),
+            resp::Value::BulkString(b"GET".to_vec()),
             resp::Value::SimpleString("key".to_string()),
         ]);
         let parsed_command = parse_command(command);
         assert!(parsed_command.is_err());
         assert_eq!(
             parsed_command.unwrap_err().to_string(),
-            "invalid command: it should be an array of bulk strings"
+            "ERR invalid command: it should be an array of bulk strings"
+        );
+    }
+
+    #[test]
+    fn test_parse_command_syntax_error() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"SET".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"value".to_vec()),
+            resp::Value::BulkString(b"FOREVER".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(parsed_command.unwrap_err(), Error::Syntax);
+    }
+
+    #[test]
+    fn test_parse_command_not_an_integer() {
+        let command = resp::Value::Array(vec![
+            resp::Value::BulkString(b"EXPIRE".to_vec()),
+            resp::Value::BulkString(b"key".to_vec()),
+            resp::Value::BulkString(b"soon".to_vec()),
+        ]);
+        let parsed_command = parse_command(command);
+        assert_eq!(
+            parsed_command.unwrap_err().to_string(),
+            "ERR value is not an integer or out of range"
         );
     }
 
     #[test]
     fn test_serialise_result_bulk_string() {
-        let result = redis::Result::BulkString("Hello".to_string());
+        let result = redis::Result::BulkString(b"Hello".to_vec());
         let serialised = serialise_result(result);
-        assert_eq!(serialised, resp::Value::BulkString("Hello".to_string()));
+        assert_eq!(serialised, resp::Value::BulkString(b"Hello".to_vec()));
     }
 
     #[test]
@@ -510,4 +1807,32 @@ mod tests {
         let serialised = serialise_result(result);
         assert_eq!(serialised, resp::Value::SimpleString("OK".to_string()));
     }
+
+    #[test]
+    fn test_serialise_result_error() {
+        let result = redis::Result::Error(redis::Error::WrongType);
+        let serialised = serialise_result(result);
+        assert_eq!(
+            serialised,
+            resp::Value::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_serialise_result_map() {
+        let result = redis::Result::Map(vec![(
+            redis::Result::BulkString(b"proto".to_vec()),
+            redis::Result::Integer(3),
+        )]);
+        let serialised = serialise_result(result);
+        assert_eq!(
+            serialised,
+            resp::Value::Map(vec![(
+                resp::Value::BulkString(b"proto".to_vec()),
+                resp::Value::Integer(3),
+            )])
+        );
+    }
 }
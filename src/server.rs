@@ -1,50 +1,382 @@
-use async_net::{AsyncToSocketAddrs, TcpListener, TcpStream};
+use async_net::{TcpListener, TcpStream};
+use futures_lite::future;
 use smol::{
-    LocalExecutor,
-    io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+    Executor,
+    channel::{Receiver, Sender},
+    io::{AsyncWriteExt, BufWriter},
 };
-use std::sync::Arc;
-
-use crate::{dashmap, redis, resp, resp_cmd};
-
-pub fn start<A: AsyncToSocketAddrs>(addr: A) -> std::io::Result<()> {
-    let ex = LocalExecutor::new();
-    smol::block_on(ex.run(async {
-        let engine = dashmap::Engine::new();
-        let engine_pointer = Arc::new(engine);
-        let listener = TcpListener::bind(addr).await?;
-        loop {
-            let (socket, _) = listener.accept().await?;
-            let clone = engine_pointer.clone();
-            ex.spawn(async move { handle_client(clone, socket).await.unwrap() })
-                .detach();
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use crate::{clients, commands, config, dashmap, redis, resp, resp_cmd};
+
+pub fn start(config: config::Config) -> std::io::Result<()> {
+    let addrs = config.addrs()?;
+    Server::with_config(config).bind(addrs.as_slice())?.wait()
+}
+
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+pub struct Server {
+    config: config::Config,
+    limits: resp::Limits,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::with_config(config::Config::default())
+    }
+
+    // The address to listen on is given to `bind`: `bind` and `port` in
+    // `config` are only reported by CONFIG GET.
+    pub fn with_config(config: config::Config) -> Self {
+        let limits = resp::Limits {
+            max_bulk_len: config.proto_max_bulk_len,
+            ..resp::Limits::default()
+        };
+        Server { config, limits }
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.io_threads = threads.max(1);
+        self
+    }
+
+    pub fn limits(mut self, limits: resp::Limits) -> Self {
+        self.config.proto_max_bulk_len = limits.max_bulk_len;
+        self.limits = limits;
+        self
+    }
+
+    // The listener is bound before this returns, so the server is ready to
+    // accept connections as soon as the handle is available.
+    pub fn bind<A: ToSocketAddrs>(self, addr: A) -> std::io::Result<Handle> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let listener = TcpListener::try_from(listener)?;
+
+        let threads_count = self.config.io_threads.max(1);
+        let ex = Arc::new(Executor::new());
+        let engine = Arc::new(dashmap::Engine::with_config(self.config));
+        let registry = Arc::new(clients::Registry::new());
+        // closing a channel wakes up every receiver: `stop` ends the accept
+        // loop, `finished` ends the worker threads once clients are drained
+        let (stop, stopped) = smol::channel::bounded::<()>(1);
+        let (finished, workers_stopped) = smol::channel::bounded::<()>(1);
+        ex.spawn(expire_keys(
+            engine.clone(),
+            registry.clone(),
+            stopped.clone(),
+        ))
+        .detach();
+
+        let mut threads = Vec::with_capacity(threads_count);
+        for _ in 1..threads_count {
+            let ex = ex.clone();
+            let workers_stopped = workers_stopped.clone();
+            threads.push(std::thread::spawn(move || {
+                let _ = smol::block_on(ex.run(workers_stopped.recv()));
+                Ok(())
+            }));
+        }
+        let limits = self.limits;
+        threads.push(std::thread::spawn(move || {
+            let result =
+                smol::block_on(ex.run(serve(&ex, listener, engine, registry, limits, stopped)));
+            drop(finished);
+            result
+        }));
+
+        Ok(Handle {
+            local_addr,
+            stop,
+            threads,
+        })
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Dropping the handle stops the server without waiting for it.
+pub struct Handle {
+    local_addr: SocketAddr,
+    stop: Sender<()>,
+    threads: Vec<JoinHandle<std::io::Result<()>>>,
+}
+
+impl Handle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Stops accepting connections, lets connected clients finish the commands
+    // they are running and returns once every one of them is gone.
+    pub fn shutdown(self) -> std::io::Result<()> {
+        let Handle { stop, threads, .. } = self;
+        drop(stop);
+        join(threads)
+    }
+
+    pub fn wait(self) -> std::io::Result<()> {
+        join(self.threads)
+    }
+}
+
+fn join(threads: Vec<JoinHandle<std::io::Result<()>>>) -> std::io::Result<()> {
+    let mut result = Ok(());
+    for thread in threads {
+        let r = thread
+            .join()
+            .unwrap_or_else(|_| Err(std::io::Error::other("server thread panicked")));
+        result = result.and(r);
+    }
+    result
+}
+
+async fn serve(
+    ex: &Executor<'static>,
+    listener: TcpListener,
+    engine: Arc<dashmap::Engine<'static>>,
+    registry: Arc<clients::Registry>,
+    limits: resp::Limits,
+    stopped: Receiver<()>,
+) -> std::io::Result<()> {
+    // every client holds a sender: once all of them are dropped, `drained` closes
+    let (connected, drained) = smol::channel::bounded::<()>(1);
+    let result = loop {
+        let accepted = future::or(async { Some(listener.accept().await) }, async {
+            let _ = stopped.recv().await;
+            None
+        })
+        .await;
+        let socket = match accepted {
+            Some(Ok((socket, _))) => socket,
+            Some(Err(e)) => break Err(e),
+            None => break Ok(()),
+        };
+        let (maxclients, max_bulk_len) = {
+            let config = engine.config();
+            (config.maxclients, config.proto_max_bulk_len)
+        };
+        // every connected client holds a clone of `connected`
+        if connected.sender_count() > maxclients {
+            // a peer that does not read must not hold up accepting the others
+            let reply = redis::Error::Generic("max number of clients reached".to_string());
+            let mut socket = socket;
+            ex.spawn(async move {
+                let _ = socket.write_all(format!("-{}\r\n", reply).as_bytes()).await;
+            })
+            .detach();
+            continue;
+        }
+        let limits = resp::Limits {
+            max_bulk_len,
+            ..limits
+        };
+        let engine = engine.clone();
+        let registry = registry.clone();
+        let stopped = stopped.clone();
+        let connected = connected.clone();
+        ex.spawn(async move {
+            if let (Ok(addr), Ok(laddr)) = (socket.peer_addr(), socket.local_addr()) {
+                let client = registry.register(addr, laddr);
+                // a broken connection only affects its own client
+                let _ = handle_client(engine, &registry, &client, socket, limits, stopped).await;
+                registry.unregister(&client);
+            }
+            drop(connected);
+        })
+        .detach();
+    };
+    drop(listener);
+    drop(connected);
+    let _ = drained.recv().await;
+    result
+}
+
+// Runs the active expire cycle `hz` times a second, for at most a quarter of
+// each period like Redis, until the server stops.
+async fn expire_keys(
+    engine: Arc<dashmap::Engine<'static>>,
+    registry: Arc<clients::Registry>,
+    stopped: Receiver<()>,
+) {
+    loop {
+        let period = std::time::Duration::from_secs(1) / engine.config().hz as u32;
+        let tick = async {
+            smol::Timer::after(period).await;
+            // keys do not expire while writes are paused
+            registry.paused(true).await;
+            true
+        };
+        if !future::or(tick, async {
+            let _ = stopped.recv().await;
+            false
+        })
+        .await
+        {
+            break;
         }
-    }))
+        engine.active_expire_cycle(period / 4);
+    }
 }
 
-async fn handle_client<E: redis::Engine>(engine: Arc<E>, stream: TcpStream) -> std::io::Result<()> {
+async fn handle_client<E: redis::Engine>(
+    engine: Arc<E>,
+    registry: &clients::Registry,
+    client: &clients::Client,
+    stream: TcpStream,
+    limits: resp::Limits,
+    stopped: Receiver<()>,
+) -> std::io::Result<()> {
     // println!("Client connected: {}", stream.peer_addr()?);
-    let mut reader = BufReader::new(stream.clone());
+    let mut reader = stream.clone();
     let mut writer = BufWriter::new(stream.clone());
+    let mut decoder = resp::Decoder::new(limits);
+    let mut protocol = resp::Protocol::Resp2;
+    let closed = || {
+        future::or(client.killed(), async {
+            let _ = stopped.recv().await;
+        })
+    };
 
-    while has_data_left(&mut reader).await? {
-        let command = resp::parse(&mut reader).await?;
-        // println!("Received command: {:?}", command);
-        let reply = run_cmd(&engine, command);
-        resp::serialise(&mut writer, &reply).await?;
-        writer.flush().await?;
+    // a killed client still gets the replies to the commands it already ran
+    while !client.is_killed() {
+        match decoder.decode() {
+            Ok(Some(request)) => {
+                let mut args = request.args();
+                let spec = args.next().and_then(commands::lookup);
+                let write = spec.is_some_and(|s| s.flags.contains(&"write"));
+                // CLIENT is never paused, or nobody could send CLIENT UNPAUSE
+                let pausable = spec.is_none_or(|s| s.name != "client");
+                let pause = async {
+                    if pausable {
+                        registry.paused(write).await;
+                    }
+                    true
+                };
+                if !future::or(pause, async {
+                    closed().await;
+                    false
+                })
+                .await
+                {
+                    break;
+                }
+                match spec {
+                    Some(spec) if spec.container => client.interact(spec.name, args.next()),
+                    Some(spec) => client.interact(spec.name, None),
+                    None => {}
+                }
+                let reply = run_cmd(&engine, registry, client, &mut protocol, &request, spec);
+                resp::serialise(&mut writer, &reply, protocol).await?;
+            }
+            Ok(None) => {
+                // every buffered command has been answered: send the whole batch
+                writer.flush().await?;
+                let read = future::or(
+                    async { Some(decoder.read_from(&mut reader).await) },
+                    async {
+                        closed().await;
+                        None
+                    },
+                )
+                .await;
+                match read {
+                    Some(Ok(0)) | None => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e),
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                let reply = resp::Value::Error(redis::Error::Protocol(e.to_string()).to_string());
+                resp::serialise(&mut writer, &reply, protocol).await?;
+                writer.flush().await?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
     }
     // println!("Client disconnected");
-    Ok(())
+    writer.flush().await
 }
 
-fn run_cmd<E: redis::Engine>(engine: &Arc<E>, command: resp::Value) -> resp::Value {
-    resp_cmd::parse_command(command)
-        .map(|cmd| engine.call(cmd))
-        .map(resp_cmd::serialise_result)
-        .unwrap_or_else(|e| resp::Value::Error(format!("ERR {}", e)))
+fn run_cmd<E: redis::Engine>(
+    engine: &Arc<E>,
+    registry: &clients::Registry,
+    client: &clients::Client,
+    protocol: &mut resp::Protocol,
+    request: &resp::Request,
+    spec: Option<&'static commands::Spec>,
+) -> resp::Value {
+    match resp_cmd::parse_request(request, spec) {
+        Ok(redis::Command::Hello {
+            protocol: version,
+            auth,
+            setname,
+        }) => hello(engine, registry, client, protocol, version, auth, setname),
+        Ok(redis::Command::Client(cmd)) => resp_cmd::serialise_result(registry.call(client, cmd)),
+        Ok(cmd) => resp_cmd::serialise_result(engine.call(cmd)),
+        Err(e) => resp::Value::Error(e.to_string()),
+    }
 }
 
-async fn has_data_left<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<bool> {
-    reader.fill_buf().await.map(|b| !b.is_empty())
+fn hello<E: redis::Engine>(
+    engine: &Arc<E>,
+    registry: &clients::Registry,
+    client: &clients::Client,
+    protocol: &mut resp::Protocol,
+    version: Option<redis::Integer>,
+    auth: Option<(redis::String, redis::String)>,
+    setname: Option<redis::String>,
+) -> resp::Value {
+    let new_protocol = match version {
+        None => *protocol,
+        Some(redis::Integer(2)) => resp::Protocol::Resp2,
+        Some(redis::Integer(3)) => resp::Protocol::Resp3,
+        Some(_) => {
+            return resp::Value::Error(redis::Error::NoProto.to_string());
+        }
+    };
+    // no password can be configured, so like Redis' default user without
+    // one, any password is accepted for it
+    if let Some((redis::String(username), _)) = auth
+        && username != b"default"
+    {
+        return resp::Value::Error(redis::Error::WrongPass.to_string());
+    }
+    if let Some(name) = setname {
+        let result = registry.call(client, redis::ClientCommand::SetName { name });
+        if let redis::Result::Error(e) = result {
+            return resp::Value::Error(e.to_string());
+        }
+    }
+    *protocol = new_protocol;
+    client.set_protocol(protocol.version());
+    let mut reply = engine.call(redis::Command::Hello {
+        protocol: Some(redis::Integer(protocol.version())),
+        auth: None,
+        setname: None,
+    });
+    // only the server knows who is saying hello: Redis lists the id after the protocol
+    if let redis::Result::Map(fields) = &mut reply {
+        let at = fields.len().min(3);
+        fields.insert(
+            at,
+            (
+                redis::Result::BulkString(b"id".to_vec()),
+                redis::Result::Integer(client.id as i64),
+            ),
+        );
+    }
+    resp_cmd::serialise_result(reply)
 }
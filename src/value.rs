@@ -0,0 +1,317 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::redis;
+
+// A stream entry ID: milliseconds and sequence number.
+pub type StreamId = (u64, u64);
+
+// The field-value pairs of a stream entry.
+pub type StreamEntry = Vec<(Vec<u8>, Vec<u8>)>;
+
+// What a key holds. Commands only ever work on one of these, and reply with
+// WRONGTYPE when the key holds another.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Vec<u8>),
+    // A string that spells an integer the way Redis prints it, kept as the
+    // integer so that counters are not parsed and printed on every update.
+    // It is the same string to every command.
+    Integer(i64),
+    List(VecDeque<Vec<u8>>),
+    Hash(HashMap<Vec<u8>, Vec<u8>>),
+    Set(HashSet<Vec<u8>>),
+    SortedSet(HashMap<Vec<u8>, f64>),
+    Stream(BTreeMap<StreamId, StreamEntry>),
+}
+
+impl Value {
+    // A string value, encoded as an integer when it is one. Like Redis, only
+    // integers written the way it prints them are, which rules out longer
+    // strings before looking at their bytes: no sign but a minus, no leading
+    // zeros, and no "-0".
+    pub fn string(s: Vec<u8>) -> Value {
+        let digits = match s.as_slice() {
+            [b'-', digits @ ..] => digits,
+            digits => digits,
+        };
+        let canonical = match digits {
+            [b'0'] => s.len() == 1,
+            [b'1'..=b'9', ..] => s.len() <= 20,
+            _ => false,
+        };
+        if !canonical {
+            return Value::String(s);
+        }
+        match std::str::from_utf8(&s)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+        {
+            Some(n) => Value::Integer(n),
+            None => Value::String(s),
+        }
+    }
+
+    // As replied by TYPE and matched by SCAN TYPE.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) | Value::Integer(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    // An estimate of the memory held, for `maxmemory`: the contents plus a
+    // rough per-element overhead for collections.
+    pub fn size(&self) -> usize {
+        const OVERHEAD: usize = 16;
+        let bytes = |b: &Vec<u8>| b.len() + OVERHEAD;
+        match self {
+            Value::String(s) => s.len(),
+            Value::Integer(_) => 8,
+            Value::List(l) => l.iter().map(bytes).sum(),
+            Value::Hash(h) => h.iter().map(|(k, v)| bytes(k) + bytes(v)).sum(),
+            Value::Set(s) => s.iter().map(bytes).sum(),
+            Value::SortedSet(z) => z.keys().map(|m| bytes(m) + 8).sum(),
+            Value::Stream(s) => s
+                .values()
+                .map(|e| 16 + e.iter().map(|(f, v)| bytes(f) + bytes(v)).sum::<usize>())
+                .sum(),
+        }
+    }
+
+    pub fn as_string(&self) -> Result<Cow<'_, [u8]>, redis::Error> {
+        match self {
+            Value::String(s) => Ok(Cow::Borrowed(s)),
+            Value::Integer(n) => Ok(Cow::Owned(n.to_string().into_bytes())),
+            _ => Err(redis::Error::WrongType),
+        }
+    }
+
+    pub fn into_string(self) -> Result<Vec<u8>, redis::Error> {
+        match self {
+            Value::String(s) => Ok(s),
+            Value::Integer(n) => Ok(n.to_string().into_bytes()),
+            _ => Err(redis::Error::WrongType),
+        }
+    }
+
+    // Gives up the integer encoding, if any: the bytes may be edited into
+    // anything.
+    pub fn as_string_mut(&mut self) -> Result<&mut Vec<u8>, redis::Error> {
+        if let Value::Integer(n) = self {
+            *self = Value::String(n.to_string().into_bytes());
+        }
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err(redis::Error::WrongType),
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<i64, redis::Error> {
+        match self {
+            Value::Integer(n) => Ok(*n),
+            Value::String(s) => std::str::from_utf8(s)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or(redis::Error::NotAnInteger),
+            _ => Err(redis::Error::WrongType),
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f64, redis::Error> {
+        match self {
+            Value::Integer(n) => Ok(*n as f64),
+            Value::String(s) => parse_float(s).ok_or(redis::Error::NotAFloat),
+            _ => Err(redis::Error::WrongType),
+        }
+    }
+}
+
+// Like Redis, only accepts finite numbers without surrounding spaces.
+pub fn parse_float(v: &[u8]) -> Option<f64> {
+    std::str::from_utf8(v)
+        .ok()
+        .filter(|s| !s.starts_with(char::is_whitespace) && !s.ends_with(char::is_whitespace))
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| f.is_finite())
+}
+
+// Like Redis' "%.17Lf" with the trailing zeros trimmed: 17 fractional
+// digits at most, no exponent, and no negative zero. Redis computes with long
+// doubles, which hides the rounding errors doubles show in their last digit
+// or two: a result within a few ulps of its 15-digit rounding is printed as
+// the rounding, so that 0.1 + 0.2 is 0.3 as it is in Redis, and any other as
+// the shortest decimal that reads back as the same double.
+pub fn format_float(f: f64) -> String {
+    let rounded = format!("{:.14e}", f);
+    let ulp = f64::EPSILON * f.abs();
+    let decimal = match rounded.parse::<f64>() {
+        Ok(r) if (r - f).abs() <= 4.0 * ulp => rounded,
+        _ => format!("{:e}", f),
+    };
+    let (mantissa, exponent) = decimal.split_once('e').unwrap_or((&decimal, "0"));
+    let exponent: i64 = exponent.parse().unwrap_or(0);
+    let negative = mantissa.starts_with('-');
+    let mut digits: Vec<u8> = mantissa.bytes().filter(u8::is_ascii_digit).collect();
+
+    // the first `point` digits go before the decimal point
+    if exponent < -1 {
+        let zeros = std::iter::repeat_n(b'0', (-1 - exponent) as usize);
+        digits.splice(0..0, zeros);
+    }
+    let mut point = (exponent + 1).max(0) as usize;
+    if digits.len() < point {
+        digits.resize(point, b'0');
+    }
+    if digits.len() > point + 17 {
+        let round_up = digits[point + 17] >= b'5';
+        digits.truncate(point + 17);
+        if round_up {
+            match digits.iter().rposition(|&d| d != b'9') {
+                Some(i) => {
+                    digits[i] += 1;
+                    digits[i + 1..].fill(b'0');
+                }
+                None => {
+                    digits.fill(b'0');
+                    digits.insert(0, b'1');
+                    point += 1;
+                }
+            }
+        }
+    }
+
+    let (integer, fraction) = digits.split_at(point);
+    let integer = std::str::from_utf8(integer).unwrap_or_default();
+    let integer = match integer.trim_start_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    };
+    let fraction = std::str::from_utf8(fraction)
+        .unwrap_or_default()
+        .trim_end_matches('0');
+    let mut result = if fraction.is_empty() {
+        integer.to_string()
+    } else {
+        format!("{}.{}", integer, fraction)
+    };
+    if negative && result != "0" {
+        result.insert(0, '-');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(Value::String(b"v".to_vec()).type_name(), "string");
+        assert_eq!(Value::Integer(1).type_name(), "string");
+        assert_eq!(Value::List(VecDeque::new()).type_name(), "list");
+        assert_eq!(Value::Hash(HashMap::new()).type_name(), "hash");
+        assert_eq!(Value::Set(HashSet::new()).type_name(), "set");
+        assert_eq!(Value::SortedSet(HashMap::new()).type_name(), "zset");
+        assert_eq!(Value::Stream(BTreeMap::new()).type_name(), "stream");
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(Value::String(b"value".to_vec()).size(), 5);
+        let list = Value::List(VecDeque::from([b"a".to_vec(), b"bc".to_vec()]));
+        assert_eq!(list.size(), 3 + 2 * 16);
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(parse_float(b"1.5"), Some(1.5));
+        assert_eq!(parse_float(b"-10"), Some(-10.0));
+        assert_eq!(parse_float(b"5.0e3"), Some(5000.0));
+        assert_eq!(parse_float(b"inf"), None);
+        assert_eq!(parse_float(b"nan"), None);
+        assert_eq!(parse_float(b" 1"), None);
+        assert_eq!(parse_float(b""), None);
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(10.5 + 0.1), "10.6");
+        assert_eq!(format_float(0.1 + 0.2), "0.3");
+        assert_eq!(format_float(3.0), "3");
+        assert_eq!(format_float(5.0e3), "5000");
+        assert_eq!(format_float(-1.25), "-1.25");
+        assert_eq!(format_float(1.2345678901234567), "1.2345678901234567");
+        assert_eq!(format_float(1e20), "100000000000000000000");
+        assert_eq!(format_float(0.0), "0");
+        assert_eq!(format_float(-0.0), "0");
+
+        // 17 fractional digits at most, rounded
+        assert_eq!(format_float(1e-20), "0");
+        assert_eq!(format_float(-1e-20), "0");
+        assert_eq!(format_float(1e-17), "0.00000000000000001");
+        assert_eq!(format_float(6e-18), "0.00000000000000001");
+        assert_eq!(format_float(1.23e-10), "0.000000000123");
+        assert_eq!(format_float(0.999999999999999999), "1");
+        assert_eq!(format_float(0.12345678901234568), "0.12345678901234568");
+
+        // large exponents are written out in full
+        assert_eq!(format_float(1.5e30), "1500000000000000000000000000000");
+        assert_eq!(format_float(-2e25), "-20000000000000000000000000");
+        assert_eq!(format_float(f64::MAX).len(), 309);
+    }
+
+    #[test]
+    fn test_integer_encoding() {
+        assert_eq!(Value::string(b"42".to_vec()), Value::Integer(42));
+        assert_eq!(Value::string(b"-7".to_vec()), Value::Integer(-7));
+        let min = i64::MIN.to_string().into_bytes();
+        assert_eq!(Value::string(min), Value::Integer(i64::MIN));
+        // only the way Redis prints integers
+        assert_eq!(Value::string(b"0".to_vec()), Value::Integer(0));
+        for s in [
+            "042",
+            "+1",
+            "-0",
+            "-",
+            " 1",
+            "1 ",
+            "1.0",
+            "9223372036854775808",
+            "-9223372036854775809",
+            "000000000000000000001",
+            "",
+        ] {
+            let s = s.as_bytes().to_vec();
+            assert_eq!(Value::string(s.clone()), Value::String(s));
+        }
+
+        let mut value = Value::Integer(42);
+        assert_eq!(value.as_string(), Ok(Cow::Borrowed(b"42".as_slice())));
+        assert_eq!(value.as_integer(), Ok(42));
+        assert_eq!(value.as_float(), Ok(42.0));
+        value.as_string_mut().unwrap().push(b'x');
+        assert_eq!(value, Value::String(b"42x".to_vec()));
+        assert_eq!(value.as_integer(), Err(redis::Error::NotAnInteger));
+        assert_eq!(value.as_float(), Err(redis::Error::NotAFloat));
+        assert_eq!(Value::Integer(-1).into_string(), Ok(b"-1".to_vec()));
+    }
+
+    #[test]
+    fn test_as_string() {
+        let mut value = Value::String(b"v".to_vec());
+        assert_eq!(value.as_string(), Ok(Cow::Borrowed(b"v".as_slice())));
+        value.as_string_mut().unwrap().push(b'w');
+        assert_eq!(value, Value::String(b"vw".to_vec()));
+
+        let mut value = Value::List(VecDeque::new());
+        assert_eq!(value.as_string(), Err(redis::Error::WrongType));
+        assert_eq!(value.as_string_mut(), Err(redis::Error::WrongType));
+        assert_eq!(value.as_integer(), Err(redis::Error::WrongType));
+        assert_eq!(value.clone().into_string(), Err(redis::Error::WrongType));
+    }
+}
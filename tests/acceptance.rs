@@ -1,17 +1,45 @@
 use anyhow::Result;
 
-fn connection() -> Result<redis::Connection> {
-    static ADDR: std::sync::OnceLock<String> = std::sync::OnceLock::new();
-    let addr = ADDR.get_or_init(|| {
-        std::env::var("REDIS_ADDR").unwrap_or_else(|_| {
-            std::thread::spawn(|| rosso::server::start("127.0.0.1:6379").unwrap());
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            "redis://127.0.0.1:6379".to_string()
+// Every test gets its own server on an ephemeral port, unless REDIS_ADDR
+// points the whole suite at an external one.
+struct TestServer {
+    address: String,
+    handle: Option<rosso::server::Handle>,
+}
+
+impl TestServer {
+    fn start() -> Result<Self> {
+        if let Ok(url) = std::env::var("REDIS_ADDR") {
+            let address = url.trim_start_matches("redis://").to_string();
+            return Ok(TestServer {
+                address,
+                handle: None,
+            });
+        }
+        let handle = rosso::server::Server::new().bind("127.0.0.1:0")?;
+        Ok(TestServer {
+            address: handle.local_addr().to_string(),
+            handle: Some(handle),
         })
-    });
-    let client = redis::Client::open(addr.as_str())?;
-    let connection = client.get_connection()?;
-    Ok(connection)
+    }
+
+    fn url(&self) -> String {
+        format!("redis://{}", self.address)
+    }
+
+    fn connection(&self) -> Result<redis::Connection> {
+        let client = redis::Client::open(self.url())?;
+        let connection = client.get_connection()?;
+        Ok(connection)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown().unwrap();
+        }
+    }
 }
 
 fn random_key_name() -> String {
@@ -22,8 +50,9 @@ fn random_key_name() -> String {
 
 #[test]
 fn test_strings() -> Result<()> {
+    let server = TestServer::start()?;
     let key_name = random_key_name();
-    let mut con = connection()?;
+    let mut con = server.connection()?;
 
     let res: Option<String> = redis::cmd("SET")
         .arg(&key_name)
@@ -59,8 +88,9 @@ fn test_strings() -> Result<()> {
 
 #[test]
 fn test_expiration() -> Result<()> {
+    let server = TestServer::start()?;
     let key_name = random_key_name();
-    let mut con = connection()?;
+    let mut con = server.connection()?;
 
     redis::cmd("SET")
         .arg(&key_name)
@@ -90,17 +120,18 @@ fn test_expiration() -> Result<()> {
 
 #[test]
 fn test_concurrent_incrs() -> Result<()> {
+    let server = TestServer::start()?;
     let key_name = random_key_name();
-    let mut con = connection()?;
+    let mut con = server.connection()?;
 
     redis::cmd("SET").arg(&key_name).arg(42).exec(&mut con)?;
 
     let mut children = vec![];
     for _ in 0..10 {
         let k = key_name.clone();
+        let client = redis::Client::open(server.url())?;
         children.push(std::thread::spawn(move || {
-            let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-            let mut con = client.clone().get_connection().unwrap();
+            let mut con = client.get_connection().unwrap();
             redis::cmd("INCR").arg(&k).exec(&mut con).unwrap();
         }));
     }
@@ -117,6 +148,7 @@ fn test_concurrent_incrs() -> Result<()> {
 
 #[test]
 fn test_concurrent_sets_with_nx() -> Result<()> {
+    let server = TestServer::start()?;
     let key_name = random_key_name();
     let mut children = vec![];
     let (sender, receiver) = std::sync::mpsc::channel();
@@ -124,8 +156,9 @@ fn test_concurrent_sets_with_nx() -> Result<()> {
     for _ in 0..100 {
         let s = sender.clone();
         let k = key_name.clone();
+        let client = redis::Client::open(server.url())?;
         children.push(std::thread::spawn(move || {
-            let mut con = connection().unwrap();
+            let mut con = client.get_connection().unwrap();
             for _ in 0..100 {
                 s.send(
                     redis::cmd("SET")
@@ -153,7 +186,7 @@ fn test_concurrent_sets_with_nx() -> Result<()> {
 
     assert_eq!(1, count);
 
-    let mut con = connection()?;
+    let mut con = server.connection()?;
     let value: i32 = redis::cmd("GET").arg(&key_name).query(&mut con)?;
     assert_eq!(42, value);
 
@@ -162,8 +195,9 @@ fn test_concurrent_sets_with_nx() -> Result<()> {
 
 #[test]
 fn test_case_insenstivity() -> Result<()> {
+    let server = TestServer::start()?;
     let key_name = random_key_name();
-    let mut con = connection()?;
+    let mut con = server.connection()?;
 
     redis::cmd("set")
         .arg(&key_name)
@@ -173,3 +207,905 @@ fn test_case_insenstivity() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_resp3() -> Result<()> {
+    let server = TestServer::start()?;
+    let key_name = random_key_name();
+    let mut con = server.connection()?;
+    let client = redis::Client::open(format!("{}/?protocol=resp3", server.url()))?;
+    let mut con3 = client.get_connection()?;
+
+    let value: Option<String> = redis::cmd("GET").arg(&key_name).query(&mut con3)?;
+    assert_eq!(None, value);
+
+    redis::cmd("SET").arg(&key_name).arg(42).exec(&mut con3)?;
+    let value: i32 = redis::cmd("GET").arg(&key_name).query(&mut con)?;
+    assert_eq!(42, value);
+
+    let hello: std::collections::HashMap<String, redis::Value> =
+        redis::cmd("HELLO").arg(3).query(&mut con3)?;
+    assert_eq!(Some(&redis::Value::Int(3)), hello.get("proto"));
+
+    let value: Option<String> = redis::cmd("GET").arg(random_key_name()).query(&mut con)?;
+    assert_eq!(None, value);
+
+    Ok(())
+}
+
+#[test]
+fn test_binary_values() -> Result<()> {
+    let server = TestServer::start()?;
+    let key_name = [random_key_name().as_bytes(), b"\xff\x00\r\n"].concat();
+    let mut con = server.connection()?;
+
+    let value = b"\x00\xc3\x28\r\n\xff".to_vec();
+    redis::cmd("SET")
+        .arg(&key_name)
+        .arg(&value)
+        .exec(&mut con)?;
+
+    let len: usize = redis::cmd("STRLEN").arg(&key_name).query(&mut con)?;
+    assert_eq!(6, len);
+
+    let new_len: usize = redis::cmd("APPEND")
+        .arg(&key_name)
+        .arg(b"\xfe")
+        .query(&mut con)?;
+    assert_eq!(7, new_len);
+
+    let stored: Vec<u8> = redis::cmd("GET").arg(&key_name).query(&mut con)?;
+    assert_eq!(b"\x00\xc3\x28\r\n\xff\xfe".to_vec(), stored);
+
+    Ok(())
+}
+
+#[test]
+fn test_inline_commands() -> Result<()> {
+    use std::io::{BufRead, Write};
+
+    let server = TestServer::start()?;
+    let key_name = random_key_name();
+    let stream = std::net::TcpStream::connect(&server.address)?;
+    let mut reader = std::io::BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    write!(
+        writer,
+        "PING\r\n\r\nSET {} \"hello world\"\nGET {}\r\n",
+        key_name, key_name
+    )?;
+
+    let mut lines = vec![];
+    for _ in 0..4 {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        lines.push(line);
+    }
+    assert_eq!(
+        vec!["+PONG\r\n", "+OK\r\n", "$11\r\n", "hello world\r\n"],
+        lines
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_protocol_errors() -> Result<()> {
+    use std::io::{Read, Write};
+
+    let server = TestServer::start()?;
+    for (request, error) in [
+        (&b"*abc\r\n"[..], "invalid multibulk length"),
+        (b"*1\r\n$1073741824\r\n", "invalid bulk length"),
+        (b"GET \"key\r\n", "unbalanced quotes in request"),
+    ] {
+        let mut stream = std::net::TcpStream::connect(&server.address)?;
+        stream.write_all(request)?;
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        assert_eq!(format!("-ERR Protocol error: {}\r\n", error), reply);
+    }
+
+    let mut con = server.connection()?;
+    let pong: String = redis::cmd("PING").query(&mut con)?;
+    assert_eq!("PONG", pong);
+
+    Ok(())
+}
+
+#[test]
+fn test_pipelining() -> Result<()> {
+    let server = TestServer::start()?;
+    let key_name = random_key_name();
+    let mut con = server.connection()?;
+
+    let mut pipe = redis::pipe();
+    for i in 0..5_000 {
+        pipe.cmd("INCR").arg(&key_name);
+        pipe.cmd("GET").arg(&key_name);
+        pipe.cmd("PING").arg(i);
+    }
+    let replies: Vec<(i64, i64, i64)> = pipe.query(&mut con)?;
+
+    assert_eq!(5_000, replies.len());
+    for (i, (incr, get, ping)) in replies.into_iter().enumerate() {
+        let expected = i as i64 + 1;
+        assert_eq!((expected, expected, i as i64), (incr, get, ping));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_errors() -> Result<()> {
+    let server = TestServer::start()?;
+    let key_name = random_key_name();
+    let mut con = server.connection()?;
+
+    redis::cmd("SET").arg(&key_name).arg("abc").exec(&mut con)?;
+    let err = redis::cmd("INCR")
+        .arg(&key_name)
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("ERR"), err.code());
+    assert_eq!(
+        Some("value is not an integer or out of range"),
+        err.detail()
+    );
+
+    let err = redis::cmd("GET").exec(&mut con).unwrap_err();
+    assert_eq!(
+        Some("wrong number of arguments for 'get' command"),
+        err.detail()
+    );
+
+    let err = redis::cmd("HELLO").arg(4).exec(&mut con).unwrap_err();
+    assert_eq!(Some("NOPROTO"), err.code());
+
+    Ok(())
+}
+
+#[test]
+fn test_hello_options() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connection()?;
+
+    let _: redis::Value = redis::cmd("HELLO")
+        .arg(3)
+        .arg("AUTH")
+        .arg("default")
+        .arg("anything")
+        .arg("SETNAME")
+        .arg("app")
+        .query(&mut con)?;
+    let name: String = redis::cmd("CLIENT").arg("GETNAME").query(&mut con)?;
+    assert_eq!("app", name);
+
+    let err = redis::cmd("HELLO")
+        .arg(3)
+        .arg("AUTH")
+        .arg("nobody")
+        .arg("anything")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("WRONGPASS"), err.code());
+
+    let err = redis::cmd("HELLO")
+        .arg(3)
+        .arg("SETNAME")
+        .arg("has space")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("ERR"), err.code());
+
+    let err = redis::cmd("HELLO")
+        .arg(2)
+        .arg("SETNAME")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("Syntax error in HELLO option 'SETNAME'"), err.detail());
+    Ok(())
+}
+
+#[test]
+fn test_command() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connection()?;
+
+    let count: usize = redis::cmd("COMMAND").arg("COUNT").query(&mut con)?;
+    assert!(count > 0);
+
+    let all: Vec<redis::Value> = redis::cmd("COMMAND").query(&mut con)?;
+    assert_eq!(count, all.len());
+
+    let info: Vec<(String, i64)> = redis::cmd("COMMAND")
+        .arg("INFO")
+        .arg("get")
+        .query::<Vec<Vec<redis::Value>>>(&mut con)?
+        .into_iter()
+        .map(|i| {
+            (
+                redis::from_redis_value(&i[0]).unwrap(),
+                redis::from_redis_value(&i[1]).unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(vec![("get".to_string(), 2)], info);
+
+    let keys: Vec<String> = redis::cmd("COMMAND")
+        .arg("GETKEYS")
+        .arg("SET")
+        .arg("key")
+        .arg("value")
+        .query(&mut con)?;
+    assert_eq!(vec!["key".to_string()], keys);
+
+    let err = redis::cmd("GET")
+        .arg("key")
+        .arg("extra")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        Some("wrong number of arguments for 'get' command"),
+        err.detail()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_isolated_servers() -> Result<()> {
+    let key_name = random_key_name();
+    let first = rosso::server::Server::new().bind("127.0.0.1:0")?;
+    let second = rosso::server::Server::new()
+        .threads(1)
+        .bind("127.0.0.1:0")?;
+    assert_ne!(first.local_addr(), second.local_addr());
+
+    let mut con =
+        redis::Client::open(format!("redis://{}", first.local_addr()))?.get_connection()?;
+    redis::cmd("SET").arg(&key_name).arg(42).exec(&mut con)?;
+
+    let mut con =
+        redis::Client::open(format!("redis://{}", second.local_addr()))?.get_connection()?;
+    let value: Option<i32> = redis::cmd("GET").arg(&key_name).query(&mut con)?;
+    assert_eq!(None, value);
+
+    first.shutdown()?;
+    second.shutdown()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_shutdown() -> Result<()> {
+    use std::io::{Read, Write};
+
+    let server = rosso::server::Server::new().bind("127.0.0.1:0")?;
+    let addr = server.local_addr();
+    let mut stream = std::net::TcpStream::connect(addr)?;
+    stream.write_all(b"PING\r\n")?;
+    let mut reply = [0; 7];
+    stream.read_exact(&mut reply)?;
+    assert_eq!(b"+PONG\r\n", &reply);
+
+    server.shutdown()?;
+
+    // the connected client is closed and no new ones are accepted
+    assert_eq!(0, stream.read(&mut reply)?);
+    assert!(std::net::TcpStream::connect(addr).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_config() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connection()?;
+
+    let () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxclients")
+        .arg(100)
+        .query(&mut con)?;
+    let config: std::collections::HashMap<String, String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("maxcl*")
+        .arg("port")
+        .query(&mut con)?;
+    assert_eq!(Some("100"), config.get("maxclients").map(String::as_str));
+    assert!(config.contains_key("port"));
+
+    let err = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxclients")
+        .arg("lots")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("ERR"), err.code());
+
+    Ok(())
+}
+
+#[test]
+fn test_maxclients() -> Result<()> {
+    use std::io::Read;
+
+    let mut config = rosso::config::Config::default();
+    config.maxclients = 1;
+    let server = rosso::server::Server::with_config(config).bind("127.0.0.1:0")?;
+    let url = format!("redis://{}", server.local_addr());
+
+    let mut con = redis::Client::open(url.as_str())?.get_connection()?;
+    let pong: String = redis::cmd("PING").query(&mut con)?;
+    assert_eq!("PONG", pong);
+
+    let mut stream = std::net::TcpStream::connect(server.local_addr())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    assert_eq!("-ERR max number of clients reached\r\n", reply);
+
+    drop(con);
+    server.shutdown()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_client() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connection()?;
+    let mut other = server.connection()?;
+
+    let () = redis::cmd("CLIENT")
+        .arg("SETNAME")
+        .arg("acceptance")
+        .query(&mut con)?;
+    let name: Option<String> = redis::cmd("CLIENT").arg("GETNAME").query(&mut con)?;
+    assert_eq!(Some("acceptance".to_string()), name);
+
+    let id: i64 = redis::cmd("CLIENT").arg("ID").query(&mut con)?;
+    let info: String = redis::cmd("CLIENT").arg("INFO").query(&mut con)?;
+    assert!(info.starts_with(&format!("id={} ", id)));
+    assert!(info.contains(" name=acceptance "));
+    assert!(info.contains(" cmd=client|info "));
+
+    let other_id: i64 = redis::cmd("CLIENT").arg("ID").query(&mut other)?;
+    let list: String = redis::cmd("CLIENT")
+        .arg("LIST")
+        .arg("ID")
+        .arg(id)
+        .arg(other_id)
+        .query(&mut con)?;
+    assert_eq!(2, list.lines().count());
+
+    let killed: i64 = redis::cmd("CLIENT")
+        .arg("KILL")
+        .arg("ID")
+        .arg(other_id)
+        .query(&mut con)?;
+    assert_eq!(1, killed);
+    assert!(redis::cmd("PING").exec(&mut other).is_err());
+
+    let list: String = redis::cmd("CLIENT").arg("LIST").query(&mut con)?;
+    assert_eq!(1, list.lines().count());
+
+    Ok(())
+}
+
+#[test]
+fn test_client_pause() -> Result<()> {
+    let server = TestServer::start()?;
+    let key_name = random_key_name();
+    let mut con = server.connection()?;
+    let mut other = server.connection()?;
+
+    let () = redis::cmd("CLIENT")
+        .arg("PAUSE")
+        .arg(200)
+        .arg("WRITE")
+        .query(&mut con)?;
+
+    let start = std::time::Instant::now();
+    let value: Option<String> = redis::cmd("GET").arg(&key_name).query(&mut other)?;
+    assert_eq!(None, value);
+    assert!(start.elapsed() < std::time::Duration::from_millis(200));
+
+    redis::cmd("SET").arg(&key_name).arg(42).exec(&mut other)?;
+    assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+
+    let () = redis::cmd("CLIENT")
+        .arg("PAUSE")
+        .arg(10_000)
+        .query(&mut con)?;
+    let unpause = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        redis::cmd("CLIENT").arg("UNPAUSE").exec(&mut con)
+    });
+    let start = std::time::Instant::now();
+    let value: i32 = redis::cmd("GET").arg(&key_name).query(&mut other)?;
+    assert_eq!(42, value);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    unpause.join().unwrap()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_keyspace() -> Result<()> {
+    let server = TestServer::start()?;
+    let (a, b, c) = (random_key_name(), random_key_name(), random_key_name());
+    let mut con = server.connection()?;
+
+    redis::cmd("SET").arg(&a).arg(1).exec(&mut con)?;
+    let exists: i32 = redis::cmd("EXISTS")
+        .arg(&a)
+        .arg(&a)
+        .arg(&b)
+        .query(&mut con)?;
+    assert_eq!(2, exists);
+
+    let kind: String = redis::cmd("TYPE").arg(&a).query(&mut con)?;
+    assert_eq!("string", kind);
+
+    let copied: i32 = redis::cmd("COPY").arg(&a).arg(&b).query(&mut con)?;
+    assert_eq!(1, copied);
+
+    redis::cmd("RENAME").arg(&b).arg(&c).exec(&mut con)?;
+    let renamed: i32 = redis::cmd("RENAMENX").arg(&c).arg(&a).query(&mut con)?;
+    assert_eq!(0, renamed);
+
+    let deleted: i32 = redis::cmd("DEL").arg(&a).arg(&b).arg(&c).query(&mut con)?;
+    assert_eq!(2, deleted);
+
+    let err = redis::cmd("RENAME")
+        .arg(&a)
+        .arg(&b)
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("no such key"), err.detail());
+
+    Ok(())
+}
+
+#[test]
+fn test_keys_and_scan() -> Result<()> {
+    let server = TestServer::start()?;
+    let prefix = random_key_name();
+    let mut con = server.connection()?;
+
+    for i in 0..50 {
+        redis::cmd("SET")
+            .arg(format!("{}:{}", prefix, i))
+            .arg(i)
+            .exec(&mut con)?;
+    }
+
+    let mut keys: Vec<String> = redis::cmd("KEYS")
+        .arg(format!("{}:1?", prefix))
+        .query(&mut con)?;
+    keys.sort();
+    let expected: Vec<_> = (10..20).map(|i| format!("{}:{}", prefix, i)).collect();
+    assert_eq!(expected, keys);
+
+    let mut scanned = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(format!("{}:*", prefix))
+            .arg("COUNT")
+            .arg(10)
+            .arg("TYPE")
+            .arg("string")
+            .query(&mut con)?;
+        scanned.extend(keys);
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+    scanned.sort();
+    let mut expected: Vec<_> = (0..50).map(|i| format!("{}:{}", prefix, i)).collect();
+    expected.sort();
+    assert_eq!(expected, scanned);
+
+    Ok(())
+}
+
+#[test]
+fn test_expiration_commands() -> Result<()> {
+    let server = TestServer::start()?;
+    let key = random_key_name();
+    let mut con = server.connection()?;
+
+    redis::cmd("SET").arg(&key).arg(1).exec(&mut con)?;
+    let set: i32 = redis::cmd("PEXPIRE")
+        .arg(&key)
+        .arg(100_000)
+        .arg("NX")
+        .query(&mut con)?;
+    assert_eq!(1, set);
+    let set: i32 = redis::cmd("EXPIRE")
+        .arg(&key)
+        .arg(50)
+        .arg("GT")
+        .query(&mut con)?;
+    assert_eq!(0, set);
+
+    let pttl: i64 = redis::cmd("PTTL").arg(&key).query(&mut con)?;
+    assert!(pttl > 99_000 && pttl <= 100_000);
+    let expiretime: i64 = redis::cmd("EXPIRETIME").arg(&key).query(&mut con)?;
+    let pexpiretime: i64 = redis::cmd("PEXPIRETIME").arg(&key).query(&mut con)?;
+    assert_eq!(expiretime, pexpiretime / 1000);
+
+    let persisted: i32 = redis::cmd("PERSIST").arg(&key).query(&mut con)?;
+    assert_eq!(1, persisted);
+    let ttl: i64 = redis::cmd("TTL").arg(&key).query(&mut con)?;
+    assert_eq!(-1, ttl);
+
+    let deleted: i32 = redis::cmd("EXPIREAT").arg(&key).arg(1).query(&mut con)?;
+    assert_eq!(1, deleted);
+    let exists: i32 = redis::cmd("EXISTS").arg(&key).query(&mut con)?;
+    assert_eq!(0, exists);
+
+    Ok(())
+}
+
+#[test]
+fn test_maxmemory() -> Result<()> {
+    let server = TestServer::start()?;
+    let mut con = server.connection()?;
+
+    for i in 0..10 {
+        redis::cmd("SET").arg(i).arg("value").exec(&mut con)?;
+    }
+    redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxmemory")
+        .arg(1)
+        .exec(&mut con)?;
+    let err = redis::cmd("SET")
+        .arg("key")
+        .arg("value")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("OOM"), err.code());
+    let value: String = redis::cmd("GET").arg(0).query(&mut con)?;
+    assert_eq!("value", value);
+
+    // evicting every key still leaves the new one over the limit
+    redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxmemory-policy")
+        .arg("allkeys-random")
+        .exec(&mut con)?;
+    redis::cmd("SET").arg("key").arg("value").exec(&mut con)?;
+    let keys: Vec<String> = redis::cmd("KEYS").arg("*").query(&mut con)?;
+    assert_eq!(vec!["key"], keys);
+
+    Ok(())
+}
+
+#[test]
+fn test_arithmetic() -> Result<()> {
+    let server = TestServer::start()?;
+    let key = random_key_name();
+    let mut con = server.connection()?;
+
+    let value: i64 = redis::cmd("INCRBY").arg(&key).arg(10).query(&mut con)?;
+    assert_eq!(10, value);
+    let value: i64 = redis::cmd("DECR").arg(&key).query(&mut con)?;
+    assert_eq!(9, value);
+    let value: i64 = redis::cmd("DECRBY").arg(&key).arg(20).query(&mut con)?;
+    assert_eq!(-11, value);
+    let value: String = redis::cmd("INCRBYFLOAT")
+        .arg(&key)
+        .arg("0.1")
+        .query(&mut con)?;
+    assert_eq!("-10.9", value);
+
+    let err = redis::cmd("INCRBY")
+        .arg(&key)
+        .arg(1)
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("ERR"), err.code());
+    assert_eq!(
+        Some("value is not an integer or out of range"),
+        err.detail()
+    );
+    let err = redis::cmd("INCRBYFLOAT")
+        .arg(&key)
+        .arg("nan")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("value is not a valid float"), err.detail());
+
+    redis::cmd("SET").arg(&key).arg(i64::MIN).exec(&mut con)?;
+    let err = redis::cmd("DECR").arg(&key).exec(&mut con).unwrap_err();
+    assert_eq!(Some("increment or decrement would overflow"), err.detail());
+
+    Ok(())
+}
+
+#[test]
+fn test_multi_key_strings() -> Result<()> {
+    let server = TestServer::start()?;
+    let (a, b, c) = (random_key_name(), random_key_name(), random_key_name());
+    let mut con = server.connection()?;
+
+    redis::cmd("MSET")
+        .arg(&a)
+        .arg(1)
+        .arg(&b)
+        .arg(2)
+        .exec(&mut con)?;
+    let values: Vec<Option<i32>> = redis::cmd("MGET").arg(&a).arg(&c).arg(&b).query(&mut con)?;
+    assert_eq!(vec![Some(1), None, Some(2)], values);
+
+    let set: i32 = redis::cmd("MSETNX")
+        .arg(&c)
+        .arg(3)
+        .arg(&a)
+        .arg(4)
+        .query(&mut con)?;
+    assert_eq!(0, set);
+    let exists: i32 = redis::cmd("EXISTS").arg(&c).query(&mut con)?;
+    assert_eq!(0, exists);
+
+    let err = redis::cmd("MSET")
+        .arg(&a)
+        .arg(1)
+        .arg(&b)
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        Some("wrong number of arguments for 'mset' command"),
+        err.detail()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_msetnx() -> Result<()> {
+    let server = TestServer::start()?;
+
+    for _ in 0..10 {
+        let keys: Vec<String> = (0..8).map(|_| random_key_name()).collect();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut children = vec![];
+        for i in 0..20 {
+            // every client sets the same keys, in its own order
+            let mut keys = keys.clone();
+            let n = keys.len();
+            keys.rotate_left(i % n);
+            let s = sender.clone();
+            let client = redis::Client::open(server.url())?;
+            children.push(std::thread::spawn(move || {
+                let mut con = client.get_connection().unwrap();
+                let mut cmd = redis::cmd("MSETNX");
+                for k in &keys {
+                    cmd.arg(k).arg(i);
+                }
+                s.send((i, cmd.query::<i32>(&mut con).unwrap())).unwrap();
+            }));
+        }
+        for child in children {
+            child.join().unwrap();
+        }
+        drop(sender);
+
+        let winners: Vec<usize> = receiver
+            .iter()
+            .filter(|(_, set)| *set == 1)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(1, winners.len());
+        let mut con = server.connection()?;
+        let values: Vec<usize> = redis::cmd("MGET").arg(&keys).query(&mut con)?;
+        assert_eq!(vec![winners[0]; keys.len()], values);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_mset_is_atomic_for_readers() -> Result<()> {
+    let server = TestServer::start()?;
+    let keys: Vec<String> = (0..8).map(|_| random_key_name()).collect();
+    let mut con = server.connection()?;
+    let mut cmd = redis::cmd("MSET");
+    for k in &keys {
+        cmd.arg(k).arg(0);
+    }
+    cmd.exec(&mut con)?;
+
+    let mut writers = vec![];
+    for i in 1..5 {
+        let keys = keys.clone();
+        let client = redis::Client::open(server.url())?;
+        writers.push(std::thread::spawn(move || {
+            let mut con = client.get_connection().unwrap();
+            for _ in 0..100 {
+                let mut cmd = redis::cmd("MSET");
+                for k in &keys {
+                    cmd.arg(k).arg(i);
+                }
+                cmd.exec(&mut con).unwrap();
+            }
+        }));
+    }
+    for _ in 0..200 {
+        let values: Vec<i32> = redis::cmd("MGET").arg(&keys).query(&mut con)?;
+        assert!(values.iter().all(|v| *v == values[0]), "{:?}", values);
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_ranges() -> Result<()> {
+    let server = TestServer::start()?;
+    let (a, b) = (random_key_name(), random_key_name());
+    let mut con = server.connection()?;
+
+    redis::cmd("SET")
+        .arg(&a)
+        .arg("Hello World")
+        .arg("EX")
+        .arg(100)
+        .exec(&mut con)?;
+    let len: i32 = redis::cmd("SETRANGE")
+        .arg(&a)
+        .arg(6)
+        .arg("Redis")
+        .query(&mut con)?;
+    assert_eq!(11, len);
+    let range: String = redis::cmd("GETRANGE")
+        .arg(&a)
+        .arg(-5)
+        .arg(-1)
+        .query(&mut con)?;
+    assert_eq!("Redis", range);
+    let range: String = redis::cmd("SUBSTR").arg(&a).arg(0).arg(4).query(&mut con)?;
+    assert_eq!("Hello", range);
+    let ttl: i64 = redis::cmd("TTL").arg(&a).query(&mut con)?;
+    assert!(ttl > 0);
+
+    redis::cmd("SET").arg(&b).arg("Yellow Red").exec(&mut con)?;
+    let lcs: String = redis::cmd("LCS").arg(&a).arg(&b).query(&mut con)?;
+    assert_eq!("ello Red", lcs);
+    let len: i32 = redis::cmd("LCS")
+        .arg(&a)
+        .arg(&b)
+        .arg("LEN")
+        .query(&mut con)?;
+    assert_eq!(8, len);
+    let range =
+        |start, end| redis::Value::Array(vec![redis::Value::Int(start), redis::Value::Int(end)]);
+    let idx: redis::Value = redis::cmd("LCS")
+        .arg(&a)
+        .arg(&b)
+        .arg("IDX")
+        .arg("MINMATCHLEN")
+        .arg(4)
+        .query(&mut con)?;
+    assert_eq!(
+        redis::Value::Array(vec![
+            redis::Value::BulkString(b"matches".to_vec()),
+            redis::Value::Array(vec![
+                redis::Value::Array(vec![range(5, 8), range(6, 9)]),
+                redis::Value::Array(vec![range(1, 4), range(1, 4)]),
+            ]),
+            redis::Value::BulkString(b"len".to_vec()),
+            redis::Value::Int(8),
+        ]),
+        idx
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_get_and_modify() -> Result<()> {
+    let server = TestServer::start()?;
+    let key = random_key_name();
+    let mut con = server.connection()?;
+
+    redis::cmd("SETEX")
+        .arg(&key)
+        .arg(100)
+        .arg("token")
+        .exec(&mut con)?;
+    let ttl: i64 = redis::cmd("TTL").arg(&key).query(&mut con)?;
+    assert!(ttl > 0 && ttl <= 100);
+    let value: String = redis::cmd("GETEX")
+        .arg(&key)
+        .arg("PERSIST")
+        .query(&mut con)?;
+    assert_eq!("token", value);
+    let ttl: i64 = redis::cmd("TTL").arg(&key).query(&mut con)?;
+    assert_eq!(-1, ttl);
+    let value: String = redis::cmd("GETEX")
+        .arg(&key)
+        .arg("PX")
+        .arg(100_000)
+        .query(&mut con)?;
+    assert_eq!("token", value);
+    let pttl: i64 = redis::cmd("PTTL").arg(&key).query(&mut con)?;
+    assert!(pttl > 99_000 && pttl <= 100_000);
+
+    let value: Option<String> = redis::cmd("GETDEL").arg(&key).query(&mut con)?;
+    assert_eq!(Some("token".to_string()), value);
+    let value: Option<String> = redis::cmd("GETDEL").arg(&key).query(&mut con)?;
+    assert_eq!(None, value);
+
+    let set: i32 = redis::cmd("SETNX").arg(&key).arg(1).query(&mut con)?;
+    assert_eq!(1, set);
+    let set: i32 = redis::cmd("SETNX").arg(&key).arg(2).query(&mut con)?;
+    assert_eq!(0, set);
+    redis::cmd("PSETEX")
+        .arg(&key)
+        .arg(100_000)
+        .arg(3)
+        .exec(&mut con)?;
+    let value: i32 = redis::cmd("GET").arg(&key).query(&mut con)?;
+    assert_eq!(3, value);
+
+    let err = redis::cmd("SETEX")
+        .arg(&key)
+        .arg(0)
+        .arg("v")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(Some("invalid expire time in 'setex' command"), err.detail());
+    let err = redis::cmd("PSETEX")
+        .arg(&key)
+        .arg(i64::MAX)
+        .arg("v")
+        .exec(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        Some("invalid expire time in 'psetex' command"),
+        err.detail()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_getdel() -> Result<()> {
+    let server = TestServer::start()?;
+    let key = random_key_name();
+    let mut con = server.connection()?;
+    redis::cmd("SET").arg(&key).arg("token").exec(&mut con)?;
+
+    // only one client may consume the token
+    let mut children = vec![];
+    for _ in 0..20 {
+        let k = key.clone();
+        let client = redis::Client::open(server.url())?;
+        children.push(std::thread::spawn(move || {
+            let mut con = client.get_connection().unwrap();
+            redis::cmd("GETDEL")
+                .arg(&k)
+                .query::<Option<String>>(&mut con)
+                .unwrap()
+        }));
+    }
+    let consumed = children
+        .into_iter()
+        .filter_map(|child| child.join().unwrap())
+        .count();
+    assert_eq!(1, consumed);
+
+    Ok(())
+}